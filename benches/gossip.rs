@@ -0,0 +1,92 @@
+//! Simulates a large gossip cluster, to check that simulations with many hosts
+//! stay practical.
+//!
+//! Run with `cargo bench --bench gossip`, optionally passing the number of
+//! hosts, e.g. `cargo bench --bench gossip -- 2000`.
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use turmoil::{lookup, net::UdpSocket, Builder};
+
+const PORT: u16 = 7946;
+
+/// How often each host gossips.
+const INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many random peers each host gossips to.
+const FANOUT: usize = 3;
+
+fn main() -> turmoil::Result {
+    let hosts = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(1000);
+
+    let mut sim = Builder::new()
+        .min_message_latency(Duration::from_millis(5))
+        .max_message_latency(Duration::from_millis(50))
+        .simulation_duration(Duration::from_secs(60))
+        // Hosts are idle between gossip rounds and message arrivals
+        .skip_idle_hosts(true)
+        .build();
+
+    for i in 0..hosts {
+        sim.host(format!("node-{i}"), move || gossip(i, hosts));
+    }
+
+    // Runs the simulation long enough for a rumor started by the first host
+    // to reach the whole cluster.
+    sim.client("observer", async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok(())
+    });
+
+    let start = Instant::now();
+    let mut steps = 0;
+    while !sim.step()? {
+        steps += 1;
+    }
+    let wall = start.elapsed();
+
+    let network = sim.stats().network();
+    println!(
+        "{hosts} hosts, {:?} simulated in {wall:?} ({steps} steps, {} messages delivered)",
+        sim.elapsed(),
+        network.messages_delivered,
+    );
+
+    Ok(())
+}
+
+/// Every [`INTERVAL`], send the newest rumor heard of to [`FANOUT`] random
+/// peers.
+async fn gossip(id: u64, hosts: u64) -> turmoil::Result {
+    let sock = UdpSocket::bind(("0.0.0.0", PORT)).await?;
+    let mut rng = SmallRng::seed_from_u64(id);
+    let mut rumor = u64::from(id == 0);
+    let mut buf = [0; 8];
+
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if rumor == 0 {
+                    continue;
+                }
+
+                for _ in 0..FANOUT {
+                    let peer = format!("node-{}", rng.gen_range(0..hosts));
+                    let dst = SocketAddr::new(lookup(peer), PORT);
+                    sock.send_to(&rumor.to_be_bytes(), dst).await?;
+                }
+            }
+            res = sock.recv_from(&mut buf) => {
+                res?;
+                rumor = rumor.max(u64::from_be_bytes(buf));
+            }
+        }
+    }
+}
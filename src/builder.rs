@@ -1,12 +1,21 @@
 use crate::*;
 
+use crate::pcap::Pcap;
+use crate::trace::Recorder;
+
 use rand::{RngCore, SeedableRng};
-use std::time::{Duration, SystemTime};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Environment variable used to replay a simulation with a specific seed.
+const SEED_ENV: &str = "TURMOIL_SEED";
 
 /// Configure the simulation
 pub struct Builder {
     rng: Option<Box<dyn RngCore>>,
 
+    seed: Option<u64>,
+
     config: Config,
 
     ip_version: IpVersion,
@@ -24,16 +33,23 @@ impl Builder {
     pub fn new() -> Self {
         Self {
             rng: None,
+            seed: None,
             config: Config::default(),
             ip_version: IpVersion::default(),
             link: config::Link {
                 latency: Some(config::Latency::default()),
                 message_loss: Some(config::MessageLoss::default()),
+                delivery: Some(config::Delivery::default()),
+                bandwidth: Some(config::Bandwidth::default()),
+                corruption: Some(Corruption::default()),
             },
         }
     }
 
-    /// When the simulation starts.
+    /// When the simulation starts. Defaults to [`UNIX_EPOCH`], so that the
+    /// same seed produces the same simulation.
+    ///
+    /// [`UNIX_EPOCH`]: std::time::UNIX_EPOCH
     pub fn epoch(&mut self, value: SystemTime) -> &mut Self {
         self.config.epoch = value;
         self
@@ -51,6 +67,71 @@ impl Builder {
         self
     }
 
+    /// Jump over periods where nothing is due to happen, rather than stepping
+    /// through them one tick at a time.
+    ///
+    /// When every host is waiting, time moves straight to the earliest of the
+    /// next timer across host runtimes, message delivery, TCP retransmission
+    /// or scheduled fault. Events happen at the same simulated times as when
+    /// ticking, so long, mostly idle, simulations finish much faster. Idle
+    /// time is not skipped while a [`Nemesis`] is injecting faults, as it
+    /// rolls for faults every tick.
+    ///
+    /// Timers can't be inspected without firing them, and a runtime's clock
+    /// can't move back, so time skips no further than a message could arrive
+    /// for any host: the minimum latency of links to it, or the next in-flight
+    /// message, and not at all while a host's connect waits to be accepted.
+    /// This has no effect at the default minimum latency of zero.
+    /// Hosts that share state outside the simulated network are the exception
+    /// to matching ticking, as with [`Builder::skip_idle_hosts`].
+    ///
+    /// A single [`Sim::step`](crate::Sim::step) may move time forward by more
+    /// than one tick.
+    pub fn event_driven(&mut self, value: bool) -> &mut Self {
+        self.config.event_driven = value;
+        self
+    }
+
+    /// Only tick hosts that have work to do, rather than every host on every
+    /// step.
+    ///
+    /// A host is ticked when messages are delivered to it, its tasks are woken,
+    /// or a timer is due. Once idle, a host's runtime moves forward to its next
+    /// timer and sits out steps until the simulation catches up, which makes
+    /// simulations with many mostly idle hosts much faster.
+    ///
+    /// Timers can't be inspected without firing them, and a runtime's clock
+    /// can't move back, so a host only moves as far ahead as nothing could
+    /// wake it: the minimum latency of links to it, or the next in-flight
+    /// message, and not at all while its connect waits to be accepted. This
+    /// keeps each host's `Instant` and timers the same as when ticking every
+    /// host.
+    ///
+    /// Hosts that share state outside the simulated network, e.g. channels,
+    /// are the exception. Woken by another host while ahead, their tasks run
+    /// early, with timers already elapsed and `Instant` ahead of the
+    /// simulation until it catches up.
+    ///
+    /// This has no effect at the default minimum latency of zero, as a
+    /// message could arrive on any step. Set [`Builder::min_message_latency`]
+    /// above zero to let hosts sit out steps.
+    pub fn skip_idle_hosts(&mut self, value: bool) -> &mut Self {
+        self.config.skip_idle_hosts = value;
+        self
+    }
+
+    /// Run hosts in a random order on each step, drawn from the simulation's
+    /// random number generator, rather than in the order they were added.
+    ///
+    /// Within a step, hosts that run earlier act first, e.g. on messages due
+    /// at the same time, so a fixed order hides races that depend on it.
+    /// Shuffling explores these interleavings across seeds. Tasks within a
+    /// host are still polled in the order tokio schedules them.
+    pub fn random_host_order(&mut self, value: bool) -> &mut Self {
+        self.config.random_host_order = value;
+        self
+    }
+
     /// Which kind of network should be simulated.
     pub fn ip_version(&mut self, value: IpVersion) -> &mut Self {
         self.ip_version = value;
@@ -63,6 +144,44 @@ impl Builder {
         self
     }
 
+    /// Record simulation events, which are available via [`Sim::trace`].
+    pub fn record_trace(&mut self, value: bool) -> &mut Self {
+        self.config.record_trace = value;
+        self
+    }
+
+    /// Capture messages delivered over the simulated network to a pcap file
+    /// at `path`, which can be opened with tools such as Wireshark.
+    ///
+    /// IP and TCP/UDP headers are synthesized for each message and packets
+    /// are timestamped with simulated time relative to the configured epoch.
+    /// Loopback traffic is not captured.
+    pub fn pcap(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.config.pcap = Some(path.into());
+        self
+    }
+
+    /// Inject faults at random as the simulation steps. See [`Nemesis`].
+    pub fn nemesis(&mut self, value: Nemesis) -> &mut Self {
+        self.config.nemesis = value;
+        self
+    }
+
+    /// Set the seed for the random number generator used to fuzz.
+    ///
+    /// If unset, the `TURMOIL_SEED` environment variable is used, falling back
+    /// to a randomly chosen seed. Either way, the seed is available via
+    /// [`Sim::seed`] and is included in simulation errors so failing runs can
+    /// be replayed.
+    ///
+    /// Tokio's own randomness, such as the order `select!` polls its branches
+    /// in, is only seeded when built with `--cfg tokio_unstable` (see the crate
+    /// docs). Without it, a replayed simulation may diverge.
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = Some(value);
+        self
+    }
+
     pub fn min_message_latency(&mut self, value: Duration) -> &mut Self {
         self.link
             .latency
@@ -81,6 +200,17 @@ impl Builder {
         self
     }
 
+    /// How message latency is distributed between the min and max message
+    /// latency.
+    ///
+    /// Panics if the distribution's parameters are invalid, e.g. a bimodal
+    /// `slow_probability` outside of `0..=1`.
+    pub fn latency_distribution(&mut self, value: LatencyDistribution) -> &mut Self {
+        value.validate();
+        self.link.latency_mut().latency_distribution = value;
+        self
+    }
+
     pub fn fail_rate(&mut self, value: f64) -> &mut Self {
         self.link.message_loss_mut().fail_rate = value;
         self
@@ -91,22 +221,184 @@ impl Builder {
         self
     }
 
+    /// Probability of a UDP datagram being delivered twice. Each copy is
+    /// delayed independently.
+    pub fn duplicate_rate(&mut self, value: f64) -> &mut Self {
+        self.link.delivery_mut().duplicate_rate = value;
+        self
+    }
+
+    /// Probability of a UDP datagram being delayed by an additional max
+    /// message latency, allowing datagrams sent after it to be delivered first.
+    pub fn reorder_rate(&mut self, value: f64) -> &mut Self {
+        self.link.delivery_mut().reorder_rate = value;
+        self
+    }
+
+    /// Limit how many payload bytes per second each link carries in each
+    /// direction. Messages queue behind one another on the link, so large
+    /// messages delay those sent after them. Bandwidth is unlimited by default.
+    pub fn bandwidth(&mut self, bytes_per_sec: u64) -> &mut Self {
+        self.link.bandwidth_mut().bytes_per_sec = Some(bytes_per_sec);
+        self
+    }
+
+    /// Probability of each message being lost, independently of other
+    /// messages. Unlike [`Builder::fail_rate`], the link stays up.
+    pub fn loss_rate(&mut self, value: f64) -> &mut Self {
+        self.link.message_loss_mut().loss_rate = value;
+        self
+    }
+
+    /// Lose messages in bursts. See [`BurstLoss`].
+    pub fn burst_loss(&mut self, value: BurstLoss) -> &mut Self {
+        self.link.message_loss_mut().burst_loss = Some(value);
+        self
+    }
+
+    /// Corrupt message payloads. See [`Corruption`].
+    pub fn corruption(&mut self, value: Corruption) -> &mut Self {
+        *self.link.corruption_mut() = value;
+        self
+    }
+
+    /// How many connections a TCP listener queues while they wait to be
+    /// accepted. A stream's received data is bounded by [`Builder::tcp_window`]
+    /// instead.
     pub fn tcp_capacity(&mut self, value: usize) -> &mut Self {
         self.config.tcp_capacity = value;
         self
     }
 
+    /// How many bytes a TCP stream's peer may send before the application has
+    /// read them. Once the window is full, writes wait until the receiving
+    /// application reads.
+    pub fn tcp_window(&mut self, value: usize) -> &mut Self {
+        assert!(value > 0, "tcp window must be greater than zero");
+
+        self.config.tcp_window = value;
+        self
+    }
+
+    /// Deliver TCP segments reliably, retransmitting those lost by the
+    /// network. See [`Retransmission`].
+    pub fn tcp_retransmission(&mut self, value: Retransmission) -> &mut Self {
+        self.config.tcp_retransmission = Some(value);
+        self
+    }
+
+    /// How long [`TcpStream::connect`] waits for the connection to be
+    /// accepted, retransmitting its SYN with exponential backoff, before
+    /// failing with [`std::io::ErrorKind::TimedOut`]. Defaults to 5 seconds,
+    /// well within the default simulation duration.
+    ///
+    /// [`TcpStream::connect`]: crate::net::TcpStream::connect
+    pub fn tcp_connect_timeout(&mut self, value: Duration) -> &mut Self {
+        self.config.tcp_connect_timeout = value;
+        self
+    }
+
     pub fn udp_capacity(&mut self, value: usize) -> &mut Self {
         self.config.udp_capacity = value;
         self
     }
 
     pub fn build<'a>(&self) -> Sim<'a> {
-        self.build_with_rng(Box::new(rand::rngs::SmallRng::from_entropy()))
+        let seed = self.resolve_seed();
+
+        tracing::info!(target: TRACING_TARGET, seed, "Seed");
+
+        let rng = Box::new(rand::rngs::SmallRng::seed_from_u64(seed));
+        self.build_sim(rng, Some(seed), self.config.record_trace)
     }
 
+    /// Build the simulation with a custom random number generator.
+    ///
+    /// The seed is unknown to the simulation in this case, so [`Sim::seed`]
+    /// returns `None`.
     pub fn build_with_rng<'a>(&self, rng: Box<dyn RngCore>) -> Sim<'a> {
-        let world = World::new(self.link.clone(), rng, self.ip_version.iter());
-        Sim::new(self.config.clone(), world)
+        self.build_sim(rng, None, self.config.record_trace)
+    }
+
+    /// Run a simulation twice with the same seed, returning an error if the
+    /// recorded traces (see [`Sim::trace`]) differ between the two runs.
+    ///
+    /// `f` is given each freshly built [`Sim`] and is responsible for
+    /// registering hosts and running it. Traces are recorded regardless of
+    /// [`Builder::record_trace`].
+    ///
+    /// Returns the result of the first run if the runs are identical.
+    ///
+    /// Tokio's own randomness is only seeded when built with
+    /// `--cfg tokio_unstable` (see [`Builder::seed`]). Without it, software
+    /// that relies on it, e.g. `select!`, may differ between runs, and a
+    /// warning is logged.
+    pub fn check_determinism(&self, f: impl Fn(&mut Sim) -> Result) -> Result {
+        let seed = self.resolve_seed();
+
+        #[cfg(not(tokio_unstable))]
+        tracing::warn!(
+            target: TRACING_TARGET,
+            "Tokio's randomness is not seeded without `--cfg tokio_unstable`, so runs may differ"
+        );
+
+        let run = || {
+            let rng = Box::new(rand::rngs::SmallRng::seed_from_u64(seed));
+            let mut sim = self.build_sim(rng, Some(seed), true);
+            let res = f(&mut sim);
+            (res, sim.trace())
+        };
+
+        let (res, first) = run();
+        let (_, second) = run();
+
+        let (first, second) = (first.records(), second.records());
+        let len = first.len().max(second.len());
+        if let Some(i) = (0..len).find(|&i| first.get(i) != second.get(i)) {
+            return Err(format!(
+                "simulation is not deterministic (seed: {seed}), event {i} differs: {:?} != {:?}",
+                first.get(i),
+                second.get(i),
+            ))?;
+        }
+
+        res
     }
+
+    fn resolve_seed(&self) -> u64 {
+        self.seed
+            .or_else(seed_from_env)
+            .unwrap_or_else(rand::random)
+    }
+
+    fn build_sim<'a>(
+        &self,
+        rng: Box<dyn RngCore>,
+        seed: Option<u64>,
+        record_trace: bool,
+    ) -> Sim<'a> {
+        let pcap = self.config.pcap.as_ref().map(|path| {
+            let since_epoch = self
+                .config
+                .epoch
+                .duration_since(UNIX_EPOCH)
+                .expect("epoch must be >= UNIX_EPOCH");
+
+            Pcap::create(path, since_epoch)
+                .unwrap_or_else(|e| panic!("failed to create {}: {e}", path.display()))
+        });
+
+        let trace = Recorder::new(record_trace, pcap);
+        let world = World::new(self.link.clone(), rng, self.ip_version.iter(), trace);
+        Sim::new(self.config.clone(), world, seed)
+    }
+}
+
+fn seed_from_env() -> Option<u64> {
+    let value = std::env::var(SEED_ENV).ok()?;
+    let seed = value
+        .parse()
+        .unwrap_or_else(|_| panic!("{SEED_ENV} must be a u64, got {value:?}"));
+
+    Some(seed)
 }
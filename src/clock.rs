@@ -0,0 +1,226 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How far a host's clock is offset from simulated time.
+///
+/// See [`Sim::set_clock_skew`](crate::Sim::set_clock_skew) and
+/// [`Sim::step_wall_clock`](crate::Sim::step_wall_clock).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClockSkew {
+    /// The host's clock is ahead of simulated time.
+    Ahead(Duration),
+
+    /// The host's clock is behind simulated time.
+    Behind(Duration),
+}
+
+impl ClockSkew {
+    fn as_nanos(&self) -> i128 {
+        match self {
+            ClockSkew::Ahead(d) => d.as_nanos() as i128,
+            ClockSkew::Behind(d) => -(d.as_nanos() as i128),
+        }
+    }
+}
+
+impl Default for ClockSkew {
+    fn default() -> Self {
+        ClockSkew::Ahead(Duration::ZERO)
+    }
+}
+
+/// Tracks how a host's local sense of time relates to simulated time.
+#[derive(Debug, Default)]
+pub(crate) struct Clock {
+    /// Wall clock time, as a duration since the unix epoch, when the host was
+    /// registered.
+    start: Duration,
+
+    skew: ClockSkew,
+
+    /// Wall clock only adjustments, e.g. NTP steps.
+    steps: i128,
+
+    /// Clock drift in parts per million. Positive values make the host's clock
+    /// run fast.
+    drift_ppm: f64,
+
+    /// Simulated time the host has been ticked for.
+    reference: Duration,
+
+    /// Local time the host has been ticked for.
+    local: Duration,
+
+    /// `local` at the start of the current tick.
+    tick_start: Duration,
+
+    /// `reference` and `local` as of the last drift change, so that changing
+    /// the drift does not retroactively apply to past ticks.
+    base: (Duration, Duration),
+}
+
+impl Clock {
+    pub(crate) fn new(start: Duration) -> Clock {
+        Clock {
+            start,
+            ..Default::default()
+        }
+    }
+
+    /// The host's wall clock time, given how much local time has passed since
+    /// the current tick started.
+    pub(crate) fn wall_time(&self, progress: Duration) -> SystemTime {
+        let now = UNIX_EPOCH + self.start + self.tick_start + progress;
+        let offset = self.skew.as_nanos() + self.steps;
+
+        if offset >= 0 {
+            now + Duration::from_nanos(offset as u64)
+        } else {
+            now - Duration::from_nanos(offset.unsigned_abs() as u64)
+        }
+    }
+
+    /// Step the wall clock without affecting the host's `Instant`.
+    pub(crate) fn step(&mut self, step: ClockSkew) {
+        self.steps += step.as_nanos();
+    }
+
+    /// Set the skew, returning how far the host's clock jumped forward, if at
+    /// all. Host clocks are monotonic, so jumps backwards are not returned.
+    pub(crate) fn set_skew(&mut self, skew: ClockSkew) -> Option<Duration> {
+        let delta = skew.as_nanos() - self.skew.as_nanos();
+        self.skew = skew;
+
+        (delta > 0).then(|| Duration::from_nanos(delta as u64))
+    }
+
+    pub(crate) fn set_drift(&mut self, ppm: f64) {
+        self.drift_ppm = ppm;
+        self.base = (self.reference, self.local);
+    }
+
+    /// Advance simulated time by `duration`, returning how much local time
+    /// passes for the host.
+    ///
+    /// Tokio timers have millisecond granularity, so drifted clocks advance in
+    /// whole milliseconds, with the fractional drift accumulated across ticks.
+    pub(crate) fn tick(&mut self, duration: Duration) -> Duration {
+        let delta = self.peek(duration);
+
+        self.reference += duration;
+        self.tick_start = self.local;
+        self.local += delta;
+        delta
+    }
+
+    /// How much local time would pass for the host if simulated time advanced
+    /// by `duration`.
+    pub(crate) fn peek(&self, duration: Duration) -> Duration {
+        if self.drift_ppm == 0.0 {
+            return duration;
+        }
+
+        let (reference, local) = self.base;
+        let target = local + (self.reference + duration - reference).mul_f64(self.rate());
+        let target = Duration::from_millis(target.as_millis() as u64);
+
+        target.saturating_sub(self.local)
+    }
+
+    /// The longest whole number of `tick`s of simulated time that passes no
+    /// more than `limit` of local time for the host.
+    pub(crate) fn ticks_within(&self, limit: Duration, tick: Duration) -> Duration {
+        let whole = |d: Duration| tick * (d.as_nanos() / tick.as_nanos()) as u32;
+
+        let mut duration = whole(limit.div_f64(self.rate().max(f64::MIN_POSITIVE)));
+        while !duration.is_zero() && self.peek(duration) > limit {
+            duration -= tick;
+        }
+        duration
+    }
+
+    fn rate(&self) -> f64 {
+        1.0 + self.drift_ppm / 1_000_000.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Clock, ClockSkew};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn drift() {
+        let tick = Duration::from_millis(1);
+
+        let mut fast = Clock::default();
+        fast.set_drift(1_000.0);
+
+        let mut slow = Clock::default();
+        slow.set_drift(-1_000.0);
+
+        let fast = (0..2_000).map(|_| fast.tick(tick)).sum::<Duration>();
+        let slow = (0..2_000).map(|_| slow.tick(tick)).sum::<Duration>();
+
+        assert_eq!(Duration::from_millis(2_002), fast);
+        assert_eq!(Duration::from_millis(1_998), slow);
+    }
+
+    #[test]
+    fn ticks_within() {
+        let tick = Duration::from_millis(1);
+
+        let mut fast = Clock::default();
+        fast.set_drift(100_000.0);
+
+        let within = fast.ticks_within(Duration::from_millis(1_000), tick);
+        assert_eq!(Duration::from_millis(909), within);
+        assert!(fast.peek(within) <= Duration::from_millis(1_000));
+        assert!(fast.peek(within + tick) > Duration::from_millis(1_000));
+
+        let plain = Clock::default();
+        assert_eq!(
+            Duration::from_millis(7),
+            plain.ticks_within(Duration::from_micros(7_500), tick)
+        );
+    }
+
+    #[test]
+    fn wall_time() {
+        let mut clock = Clock::new(Duration::from_secs(100));
+        clock.tick(Duration::from_secs(1));
+        clock.tick(Duration::from_secs(1));
+
+        let millis = |c: &Clock| {
+            c.wall_time(Duration::from_millis(500))
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis()
+        };
+
+        assert_eq!(101_500, millis(&clock));
+
+        clock.set_skew(ClockSkew::Behind(Duration::from_secs(2)));
+        assert_eq!(99_500, millis(&clock));
+
+        clock.step(ClockSkew::Ahead(Duration::from_secs(10)));
+        assert_eq!(109_500, millis(&clock));
+    }
+
+    #[test]
+    fn skew_only_jumps_forward() {
+        let mut clock = Clock::default();
+
+        assert_eq!(
+            Some(Duration::from_secs(1)),
+            clock.set_skew(ClockSkew::Ahead(Duration::from_secs(1)))
+        );
+        assert_eq!(
+            None,
+            clock.set_skew(ClockSkew::Behind(Duration::from_secs(1)))
+        );
+        assert_eq!(
+            Some(Duration::from_secs(3)),
+            clock.set_skew(ClockSkew::Ahead(Duration::from_secs(2)))
+        );
+    }
+}
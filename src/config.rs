@@ -1,5 +1,10 @@
-use rand_distr::Exp;
-use std::time::{Duration, SystemTime};
+use crate::Nemesis;
+
+use rand::{Rng, RngCore};
+use rand_distr::{Distribution, Exp, LogNormal, Normal, Pareto};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Clone)]
 pub(crate) struct Config {
@@ -9,14 +14,43 @@ pub(crate) struct Config {
     /// How much simulated time should elapse each tick
     pub(crate) tick: Duration,
 
-    /// When the simulation starts
+    /// When the simulation starts. Fixed by default, so that the same seed
+    /// produces the same simulation.
     pub(crate) epoch: SystemTime,
 
-    /// Max size of the tcp receive buffer
+    /// Max number of connections waiting to be accepted by a tcp listener
     pub(crate) tcp_capacity: usize,
 
+    /// Size of the tcp receive window, in bytes
+    pub(crate) tcp_window: usize,
+
+    /// Reliable delivery of tcp segments, if enabled
+    pub(crate) tcp_retransmission: Option<Retransmission>,
+
+    /// How long a tcp connect retransmits its SYN before giving up
+    pub(crate) tcp_connect_timeout: Duration,
+
     /// Max size of the udp receive buffer
     pub(crate) udp_capacity: usize,
+
+    /// Whether the simulation jumps over periods where nothing is due to
+    /// happen
+    pub(crate) event_driven: bool,
+
+    /// Whether hosts with nothing to do sit out steps
+    pub(crate) skip_idle_hosts: bool,
+
+    /// Whether hosts run in a random order on each step
+    pub(crate) random_host_order: bool,
+
+    /// Whether simulation events are recorded
+    pub(crate) record_trace: bool,
+
+    /// Where to write captured network traffic
+    pub(crate) pcap: Option<PathBuf>,
+
+    /// Faults injected at random as the simulation steps
+    pub(crate) nemesis: Nemesis,
 }
 
 /// Configures link behavior.
@@ -27,6 +61,15 @@ pub(crate) struct Link {
 
     /// How often sending a message works vs. the message getting dropped
     pub(crate) message_loss: Option<MessageLoss>,
+
+    /// How often datagrams are duplicated or reordered
+    pub(crate) delivery: Option<Delivery>,
+
+    /// How fast messages are put on the link
+    pub(crate) bandwidth: Option<Bandwidth>,
+
+    /// How often message payloads are corrupted
+    pub(crate) corruption: Option<Corruption>,
 }
 
 /// Configure latency behavior between two hosts.
@@ -39,9 +82,56 @@ pub(crate) struct Latency {
     pub(crate) max_message_latency: Duration,
 
     /// Probability distribution of latency within the range above.
-    pub(crate) latency_distribution: Exp<f64>,
+    pub(crate) latency_distribution: LatencyDistribution,
+}
+
+/// How message latency is distributed.
+///
+/// Sampled latencies are clamped to the configured min and max message
+/// latency, see [`Builder::min_message_latency`] and
+/// [`Builder::max_message_latency`].
+///
+/// [`Builder::min_message_latency`]: crate::Builder::min_message_latency
+/// [`Builder::max_message_latency`]: crate::Builder::max_message_latency
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum LatencyDistribution {
+    /// Exponentially distributed across the min to max range, with the given
+    /// lambda. Larger values skew latency towards the min. This is the
+    /// default, with a lambda of 5.
+    Exponential(f64),
+
+    /// Uniformly distributed between the min and max.
+    Uniform,
+
+    /// Normally distributed. Negative samples are clamped to the min.
+    Normal { mean: Duration, std_dev: Duration },
+
+    /// Log-normally distributed around `median`. Larger values of `sigma`
+    /// produce a longer tail.
+    LogNormal { median: Duration, sigma: f64 },
+
+    /// Pareto distributed, starting at `scale`. Smaller values of `shape`
+    /// produce a longer tail.
+    Pareto { scale: Duration, shape: f64 },
+
+    /// Sampled from `slow` with probability `slow_probability`, and from
+    /// `fast` otherwise.
+    Bimodal {
+        fast: Box<LatencyDistribution>,
+        slow: Box<LatencyDistribution>,
+        slow_probability: f64,
+    },
+
+    /// Always the same latency.
+    Fixed(Duration),
+
+    /// A user supplied sampler. See [`LatencyDistribution::custom`].
+    Custom(Sampler),
 }
 
+type Sampler = Arc<dyn Fn(&mut dyn RngCore) -> Duration>;
+
 /// Configure how often messages are lost
 #[derive(Clone)]
 pub(crate) struct MessageLoss {
@@ -50,6 +140,160 @@ pub(crate) struct MessageLoss {
 
     /// Probability of a failed link returning
     pub(crate) repair_rate: f64,
+
+    /// Probability of an individual message being lost
+    pub(crate) loss_rate: f64,
+
+    /// Correlated, bursty message loss
+    pub(crate) burst_loss: Option<BurstLoss>,
+}
+
+/// A two state (Gilbert-Elliott) model of bursty message loss.
+///
+/// Each direction of a link is either in the good or the bad state. Before each
+/// message is sent the link may transition between states, then the message
+/// is lost with the probability of the current state. Links start in the good
+/// state.
+///
+/// ```
+/// use turmoil::BurstLoss;
+///
+/// // Rare bursts, lasting 4 messages on average, losing half the messages
+/// let burst = BurstLoss {
+///     good_to_bad: 0.01,
+///     bad_to_good: 0.25,
+///     good_loss: 0.0,
+///     bad_loss: 0.5,
+/// };
+///
+/// let sim = turmoil::Builder::new().burst_loss(burst).build();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct BurstLoss {
+    /// Probability of moving from the good to the bad state.
+    pub good_to_bad: f64,
+
+    /// Probability of moving from the bad to the good state.
+    pub bad_to_good: f64,
+
+    /// Probability of a message being lost in the good state.
+    pub good_loss: f64,
+
+    /// Probability of a message being lost in the bad state.
+    pub bad_loss: f64,
+}
+
+/// Configure how often datagrams are duplicated or reordered. TCP segments are
+/// never duplicated or reordered.
+#[derive(Clone, Default)]
+pub(crate) struct Delivery {
+    /// Probability of a datagram being delivered twice
+    pub(crate) duplicate_rate: f64,
+
+    /// Probability of a datagram being delayed, so that datagrams sent after it
+    /// may be delivered first
+    pub(crate) reorder_rate: f64,
+}
+
+/// Configure corruption of message payloads.
+///
+/// Corruption is opt-in per protocol. Real TCP checksums catch most corruption,
+/// so TCP segments are usually left alone.
+///
+/// ```
+/// use turmoil::Corruption;
+///
+/// let corruption = Corruption {
+///     udp_rate: 0.01,
+///     ..Default::default()
+/// };
+///
+/// let sim = turmoil::Builder::new().corruption(corruption).build();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Corruption {
+    /// Probability of a UDP datagram's payload being corrupted.
+    pub udp_rate: f64,
+
+    /// Probability of a TCP data segment's payload being corrupted.
+    pub tcp_rate: f64,
+
+    /// How payloads are corrupted. One kind is chosen at random for each
+    /// corrupted message.
+    pub kinds: Vec<CorruptionKind>,
+}
+
+/// A way in which a message payload can be corrupted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CorruptionKind {
+    /// A single random bit is flipped.
+    BitFlip,
+
+    /// The payload is cut short at a random length. TCP segments keep their
+    /// length, with the bytes cut off zeroed instead.
+    Truncate,
+
+    /// A random range of bytes is zeroed.
+    ZeroFill,
+}
+
+impl Default for Corruption {
+    fn default() -> Corruption {
+        Corruption {
+            udp_rate: 0.0,
+            tcp_rate: 0.0,
+            kinds: vec![
+                CorruptionKind::BitFlip,
+                CorruptionKind::Truncate,
+                CorruptionKind::ZeroFill,
+            ],
+        }
+    }
+}
+
+/// Configure how many bytes per second a link carries, in each direction.
+#[derive(Clone, Default)]
+pub(crate) struct Bandwidth {
+    /// Bytes per second, or `None` for unlimited bandwidth
+    pub(crate) bytes_per_sec: Option<u64>,
+}
+
+/// Configure reliable delivery of TCP segments.
+///
+/// Unacknowledged segments are retransmitted after `rto`, doubling the wait
+/// after each attempt. A connection that makes no progress for `timeout` is
+/// reset, failing reads and writes with [`TimedOut`].
+///
+/// ```
+/// use std::time::Duration;
+/// use turmoil::Retransmission;
+///
+/// let sim = turmoil::Builder::new()
+///     .tcp_retransmission(Retransmission {
+///         timeout: Duration::from_secs(2),
+///         ..Default::default()
+///     })
+///     .build();
+/// ```
+///
+/// [`TimedOut`]: std::io::ErrorKind::TimedOut
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Retransmission {
+    /// How long to wait for an acknowledgement before the first
+    /// retransmission.
+    pub rto: Duration,
+
+    /// How long a connection may go without progress before it is reset.
+    pub timeout: Duration,
+}
+
+impl Default for Retransmission {
+    fn default() -> Retransmission {
+        Retransmission {
+            rto: Duration::from_millis(200),
+            timeout: Duration::from_secs(5),
+        }
+    }
 }
 
 impl Default for Config {
@@ -57,9 +301,18 @@ impl Default for Config {
         Config {
             duration: Duration::from_secs(10),
             tick: Duration::from_millis(1),
-            epoch: SystemTime::now(),
+            epoch: UNIX_EPOCH,
             tcp_capacity: 64,
+            tcp_window: 64 * 1024,
+            tcp_retransmission: None,
+            tcp_connect_timeout: Duration::from_secs(5),
             udp_capacity: 64,
+            event_driven: false,
+            skip_idle_hosts: false,
+            random_host_order: false,
+            record_trace: false,
+            pcap: None,
+            nemesis: Nemesis::default(),
         }
     }
 }
@@ -80,6 +333,30 @@ impl Link {
     pub(crate) fn message_loss_mut(&mut self) -> &mut MessageLoss {
         self.message_loss.as_mut().expect("`MessageLoss` missing")
     }
+
+    pub(crate) fn delivery(&self) -> &Delivery {
+        self.delivery.as_ref().expect("`Delivery` missing")
+    }
+
+    pub(crate) fn delivery_mut(&mut self) -> &mut Delivery {
+        self.delivery.as_mut().expect("`Delivery` missing")
+    }
+
+    pub(crate) fn bandwidth(&self) -> &Bandwidth {
+        self.bandwidth.as_ref().expect("`Bandwidth` missing")
+    }
+
+    pub(crate) fn bandwidth_mut(&mut self) -> &mut Bandwidth {
+        self.bandwidth.as_mut().expect("`Bandwidth` missing")
+    }
+
+    pub(crate) fn corruption(&self) -> &Corruption {
+        self.corruption.as_ref().expect("`Corruption` missing")
+    }
+
+    pub(crate) fn corruption_mut(&mut self) -> &mut Corruption {
+        self.corruption.as_mut().expect("`Corruption` missing")
+    }
 }
 
 impl Default for Latency {
@@ -87,16 +364,230 @@ impl Default for Latency {
         Latency {
             min_message_latency: Duration::from_millis(0),
             max_message_latency: Duration::from_millis(100),
-            latency_distribution: Exp::new(5.0).unwrap(),
+            latency_distribution: LatencyDistribution::default(),
+        }
+    }
+}
+
+impl Latency {
+    /// Sample a message latency.
+    pub(crate) fn sample(&self, rand: &mut dyn RngCore) -> Duration {
+        let (min, max) = (self.min_message_latency, self.max_message_latency);
+
+        self.latency_distribution
+            .sample(min, max, rand)
+            .clamp(min, max)
+    }
+}
+
+impl LatencyDistribution {
+    /// Sample latency with a user supplied function, driven by the
+    /// simulation's rng so that runs remain deterministic.
+    pub fn custom(f: impl Fn(&mut dyn RngCore) -> Duration + 'static) -> LatencyDistribution {
+        LatencyDistribution::Custom(Arc::new(f))
+    }
+
+    /// Panic if the distribution's parameters are invalid, e.g. a negative
+    /// lambda, so that a bad configuration fails when it is set rather than
+    /// when a message is sent.
+    pub(crate) fn validate(&self) {
+        match self {
+            LatencyDistribution::Exponential(lambda) => {
+                assert!(
+                    *lambda > 0.0,
+                    "invalid exponential latency distribution: lambda {lambda} is not positive"
+                );
+            }
+            LatencyDistribution::Normal { mean, std_dev } => {
+                Normal::new(mean.as_secs_f64(), std_dev.as_secs_f64())
+                    .expect("invalid normal latency distribution");
+            }
+            LatencyDistribution::LogNormal { median, sigma } => {
+                LogNormal::new(median.as_secs_f64().ln(), *sigma)
+                    .expect("invalid log-normal latency distribution");
+            }
+            LatencyDistribution::Pareto { scale, shape } => {
+                Pareto::new(scale.as_secs_f64(), *shape)
+                    .expect("invalid pareto latency distribution");
+            }
+            LatencyDistribution::Bimodal {
+                fast,
+                slow,
+                slow_probability,
+            } => {
+                assert!(
+                    (0.0..=1.0).contains(slow_probability),
+                    "invalid bimodal latency distribution: slow_probability {slow_probability} is not within 0..=1"
+                );
+                fast.validate();
+                slow.validate();
+            }
+            LatencyDistribution::Uniform
+            | LatencyDistribution::Fixed(_)
+            | LatencyDistribution::Custom(_) => {}
+        }
+    }
+
+    // Parameters are checked by `validate` when the distribution is set.
+    fn sample(&self, min: Duration, max: Duration, rand: &mut dyn RngCore) -> Duration {
+        // Long tails can sample values too large for a `Duration`
+        let secs = |d: f64| Duration::from_secs_f64(d.clamp(0.0, u32::MAX as f64));
+
+        match self {
+            LatencyDistribution::Exponential(lambda) => {
+                let mult = Exp::new(*lambda)
+                    .expect("invalid exponential latency distribution")
+                    .sample(rand);
+                let range = (max - min).as_millis() as f64;
+
+                min + Duration::from_millis((range * mult) as _)
+            }
+            LatencyDistribution::Uniform => rand.gen_range(min..=max),
+            LatencyDistribution::Normal { mean, std_dev } => {
+                let normal = Normal::new(mean.as_secs_f64(), std_dev.as_secs_f64())
+                    .expect("invalid normal latency distribution");
+
+                secs(normal.sample(rand))
+            }
+            LatencyDistribution::LogNormal { median, sigma } => {
+                let log_normal = LogNormal::new(median.as_secs_f64().ln(), *sigma)
+                    .expect("invalid log-normal latency distribution");
+
+                secs(log_normal.sample(rand))
+            }
+            LatencyDistribution::Pareto { scale, shape } => {
+                let pareto = Pareto::new(scale.as_secs_f64(), *shape)
+                    .expect("invalid pareto latency distribution");
+
+                secs(pareto.sample(rand))
+            }
+            LatencyDistribution::Bimodal {
+                fast,
+                slow,
+                slow_probability,
+            } => {
+                if rand.gen_bool(*slow_probability) {
+                    slow.sample(min, max, rand)
+                } else {
+                    fast.sample(min, max, rand)
+                }
+            }
+            LatencyDistribution::Fixed(latency) => *latency,
+            LatencyDistribution::Custom(f) => f(rand),
         }
     }
 }
 
+impl Default for LatencyDistribution {
+    fn default() -> LatencyDistribution {
+        LatencyDistribution::Exponential(5.0)
+    }
+}
+
 impl Default for MessageLoss {
     fn default() -> MessageLoss {
         MessageLoss {
             fail_rate: 0.0,
             repair_rate: 1.0,
+            loss_rate: 0.0,
+            burst_loss: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Latency, LatencyDistribution};
+    use crate::Builder;
+    use rand::{rngs::SmallRng, SeedableRng};
+    use std::time::Duration;
+
+    fn latency(distribution: LatencyDistribution) -> Latency {
+        Latency {
+            min_message_latency: Duration::from_millis(10),
+            max_message_latency: Duration::from_millis(200),
+            latency_distribution: distribution,
+        }
+    }
+
+    #[test]
+    fn samples_are_clamped() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let ms = Duration::from_millis;
+
+        let distributions = [
+            LatencyDistribution::default(),
+            LatencyDistribution::Uniform,
+            LatencyDistribution::Normal {
+                mean: ms(20),
+                std_dev: ms(50),
+            },
+            LatencyDistribution::LogNormal {
+                median: ms(20),
+                sigma: 2.0,
+            },
+            LatencyDistribution::Pareto {
+                scale: ms(5),
+                shape: 0.5,
+            },
+        ];
+
+        for distribution in distributions {
+            let latency = latency(distribution);
+
+            for _ in 0..1_000 {
+                let sample = latency.sample(&mut rng);
+                assert!(ms(10) <= sample && sample <= ms(200), "{sample:?}");
+            }
         }
+
+        let latency = latency(LatencyDistribution::Fixed(ms(500)));
+        assert_eq!(ms(200), latency.sample(&mut rng));
+    }
+
+    #[test]
+    fn bimodal() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let ms = Duration::from_millis;
+
+        let latency = latency(LatencyDistribution::Bimodal {
+            fast: Box::new(LatencyDistribution::Fixed(ms(20))),
+            slow: Box::new(LatencyDistribution::custom(|_| Duration::from_millis(150))),
+            slow_probability: 0.1,
+        });
+
+        let samples = (0..1_000)
+            .map(|_| latency.sample(&mut rng))
+            .collect::<Vec<_>>();
+
+        let slow = samples.iter().filter(|s| **s == ms(150)).count();
+        assert_eq!(
+            1_000 - slow,
+            samples.iter().filter(|s| **s == ms(20)).count()
+        );
+        assert!((50..150).contains(&slow), "{slow}");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid bimodal latency distribution")]
+    fn invalid_distribution_panics_when_set() {
+        Builder::new().latency_distribution(LatencyDistribution::Bimodal {
+            fast: Box::new(LatencyDistribution::Uniform),
+            slow: Box::new(LatencyDistribution::Uniform),
+            slow_probability: 1.5,
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid pareto latency distribution")]
+    fn nested_invalid_distribution_panics_when_set() {
+        Builder::new().latency_distribution(LatencyDistribution::Bimodal {
+            fast: Box::new(LatencyDistribution::Uniform),
+            slow: Box::new(LatencyDistribution::Pareto {
+                scale: Duration::from_millis(10),
+                shape: -1.0,
+            }),
+            slow_probability: 0.5,
+        });
     }
 }
@@ -1,15 +1,33 @@
 use std::{fmt::Display, net::SocketAddr};
 
-use bytes::Bytes;
-use tokio::sync::oneshot;
+use bytes::{Bytes, BytesMut};
+use tokio::sync::mpsc;
 
+/// A message in flight on the network.
 #[derive(Debug)]
-pub(crate) struct Envelope {
+pub struct Envelope {
     pub(crate) src: SocketAddr,
     pub(crate) dst: SocketAddr,
     pub(crate) message: Protocol,
 }
 
+impl Envelope {
+    /// Where the message was sent from.
+    pub fn src(&self) -> SocketAddr {
+        self.src
+    }
+
+    /// Where the message is sent to.
+    pub fn dst(&self) -> SocketAddr {
+        self.dst
+    }
+
+    /// The message [`Protocol`].
+    pub fn message(&self) -> &Protocol {
+        &self.message
+    }
+}
+
 /// Supported network protocols.
 #[derive(Debug)]
 pub enum Protocol {
@@ -27,16 +45,62 @@ pub struct Datagram(pub Bytes);
 /// scenarios, but we skip a ton of complexity (e.g. checksums, flow control,
 /// etc) because said complexity isn't useful in tests.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Segment {
     Syn(Syn),
     Data(u64, Bytes),
     Fin(u64),
+    /// Acknowledges the segments received in order, up to and including the
+    /// first seq, and reports the total number of bytes the receiving
+    /// application has read, opening the sender's window.
+    Ack(u64, u64),
     Rst,
 }
 
+/// Carries the channel the listener accepts the connection on, which is shared
+/// by retransmissions of the same SYN.
 #[derive(Debug)]
 pub struct Syn {
-    pub(crate) ack: oneshot::Sender<()>,
+    pub(crate) ack: mpsc::UnboundedSender<()>,
+}
+
+impl Protocol {
+    /// Length of the message payload in bytes.
+    pub(crate) fn payload_len(&self) -> usize {
+        match self {
+            Protocol::Tcp(Segment::Data(_, data)) => data.len(),
+            Protocol::Tcp(_) => 0,
+            Protocol::Udp(datagram) => datagram.0.len(),
+        }
+    }
+}
+
+impl Protocol {
+    /// The message payload, if it carries one.
+    pub(crate) fn payload(&self) -> Option<&Bytes> {
+        match self {
+            Protocol::Tcp(Segment::Data(_, data)) => Some(data),
+            Protocol::Tcp(_) => None,
+            Protocol::Udp(datagram) => Some(&datagram.0),
+        }
+    }
+
+    /// Replace the message payload, if it carries one.
+    ///
+    /// TCP segments keep their length, as the receive window is accounted in
+    /// bytes sent and read: the payload is padded with zeros or cut short.
+    pub(crate) fn set_payload(&mut self, payload: Bytes) {
+        match self {
+            Protocol::Tcp(Segment::Data(_, data)) if payload.len() != data.len() => {
+                let mut resized = BytesMut::from(&payload[..]);
+                resized.resize(data.len(), 0);
+                *data = resized.freeze();
+            }
+            Protocol::Tcp(Segment::Data(_, data)) => *data = payload,
+            Protocol::Tcp(_) => {}
+            Protocol::Udp(datagram) => datagram.0 = payload,
+        }
+    }
 }
 
 impl Display for Protocol {
@@ -60,6 +124,7 @@ impl Display for Segment {
             Segment::Syn(_) => write!(f, "TCP SYN"),
             Segment::Data(_, data) => hex("TCP", data, f),
             Segment::Fin(_) => write!(f, "TCP FIN"),
+            Segment::Ack(seq, read) => write!(f, "TCP ACK {seq} READ {read}"),
             Segment::Rst => write!(f, "TCP RST"),
         }
     }
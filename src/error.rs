@@ -6,3 +6,30 @@
 ///
 /// [`Result`]: std::result::Result
 pub type Result<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Wraps an error that failed the simulation with the seed required to replay
+/// it.
+#[derive(Debug)]
+pub(crate) struct SeedError {
+    seed: u64,
+    source: Box<dyn std::error::Error>,
+}
+
+impl SeedError {
+    pub(crate) fn new(seed: u64, source: Box<dyn std::error::Error>) -> SeedError {
+        SeedError { seed, source }
+    }
+}
+
+impl std::fmt::Display for SeedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let SeedError { seed, source } = self;
+        write!(f, "{source} (replay with TURMOIL_SEED={seed})")
+    }
+}
+
+impl std::error::Error for SeedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
@@ -1,5 +1,8 @@
+use crate::clock::Clock;
+use crate::config::Retransmission;
 use crate::envelope::{hex, Datagram, Protocol, Segment, Syn};
 use crate::net::{SocketPair, TcpListener, UdpSocket};
+use crate::stats::HostStats;
 use crate::world::World;
 use crate::{Envelope, TRACING_TARGET};
 
@@ -10,6 +13,8 @@ use std::fmt::Display;
 use std::io;
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::SystemTime;
 use tokio::sync::{mpsc, Notify};
 use tokio::time::{Duration, Instant};
 
@@ -37,17 +42,43 @@ pub(crate) struct Host {
 
     /// Set each time the software is run.
     now: Option<Instant>,
+
+    /// The host's local sense of time, which may be skewed or drift.
+    pub(crate) clock: Clock,
+
+    /// Counters for the messages the host sent and received.
+    pub(crate) stats: HostStats,
+
+    /// Whether another host woke the host's software directly, rather than
+    /// by sending it a message, e.g. by accepting its connection.
+    pub(crate) woken: bool,
 }
 
 impl Host {
-    pub(crate) fn new(addr: IpAddr, tcp_capacity: usize, udp_capacity: usize) -> Host {
+    pub(crate) fn new(
+        addr: IpAddr,
+        since_epoch: Duration,
+        tcp_capacity: usize,
+        tcp_window: usize,
+        tcp_retransmission: Option<Retransmission>,
+        tcp_connect_timeout: Duration,
+        udp_capacity: usize,
+    ) -> Host {
         Host {
             addr,
             udp: Udp::new(udp_capacity),
-            tcp: Tcp::new(tcp_capacity),
+            tcp: Tcp::new(
+                tcp_capacity,
+                tcp_window,
+                tcp_retransmission,
+                tcp_connect_timeout,
+            ),
             next_ephemeral_port: 49152,
             elapsed: Duration::ZERO,
             now: None,
+            clock: Clock::new(since_epoch),
+            stats: HostStats::default(),
+            woken: false,
         }
     }
 
@@ -68,6 +99,12 @@ impl Host {
         self.elapsed + run_duration
     }
 
+    /// Returns the host's wall clock time.
+    pub(crate) fn now_system_time(&self) -> SystemTime {
+        let progress = self.now.expect("host instant not set").elapsed();
+        self.clock.wall_time(progress)
+    }
+
     pub(crate) fn assign_ephemeral_port(&mut self) -> u16 {
         // Check for existing binds to avoid port conflicts
         loop {
@@ -104,14 +141,26 @@ impl Host {
         match message {
             Protocol::Tcp(segment) => self.tcp.receive_from_network(src, dst, segment),
             Protocol::Udp(datagram) => {
-                self.udp.receive_from_network(src, dst, datagram);
+                self.udp
+                    .receive_from_network(src, dst, datagram, &mut self.stats);
                 Ok(())
             }
         }
     }
 
-    pub(crate) fn tick(&mut self, duration: Duration) {
-        self.elapsed += duration
+    /// How long until TCP segments are due to be sent, if any are waiting.
+    pub(crate) fn next_timer(&self) -> Option<Duration> {
+        self.tcp
+            .next_retransmit()
+            .map(|at| at.saturating_sub(self.elapsed))
+    }
+
+    /// Advance the host's elapsed time by `duration`, returning the TCP
+    /// segments that are due to be sent, such as acknowledgements and
+    /// retransmissions.
+    pub(crate) fn tick(&mut self, duration: Duration) -> Vec<(SocketPair, Segment)> {
+        self.elapsed += duration;
+        self.tcp.poll_retransmit(self.elapsed)
     }
 }
 
@@ -123,6 +172,23 @@ pub fn elapsed() -> Duration {
     World::current(|world| world.current_host_mut().elapsed())
 }
 
+/// Returns the wall clock time of the currently executing host.
+///
+/// This is the simulated equivalent of [`SystemTime::now`], starting from the
+/// configured epoch and moving forward with the host's clock, including any
+/// skew, drift and steps applied to it.
+///
+/// Must be called from within a Turmoil simulation.
+pub fn now() -> SystemTime {
+    World::current(|world| world.current_host_mut().now_system_time())
+}
+
+/// How long to wait before resending a window update, doubling after each
+/// resend up to [`MAX_PERSIST_INTERVAL`].
+const PERSIST_INTERVAL: Duration = Duration::from_millis(200);
+
+const MAX_PERSIST_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Simulated UDP host software.
 pub(crate) struct Udp {
     /// Bound udp sockets
@@ -168,7 +234,13 @@ impl Udp {
         Ok(UdpSocket::new(addr, rx))
     }
 
-    fn receive_from_network(&mut self, src: SocketAddr, dst: SocketAddr, datagram: Datagram) {
+    fn receive_from_network(
+        &mut self,
+        src: SocketAddr,
+        dst: SocketAddr,
+        datagram: Datagram,
+        stats: &mut HostStats,
+    ) {
         if let Some(bind) = self.binds.get_mut(&dst.port()) {
             if !matches(bind.bind_addr, dst) {
                 tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %Protocol::Udp(datagram), "Dropped (Addr not bound)");
@@ -179,6 +251,7 @@ impl Udp {
                 match err {
                     mpsc::error::TrySendError::Full((datagram, _)) => {
                         tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %Protocol::Udp(datagram), "Dropped (Full buffer)");
+                        stats.udp_dropped_full += 1;
                     }
                     mpsc::error::TrySendError::Closed((datagram, _)) => {
                         tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %Protocol::Udp(datagram), "Dropped (Receiver closed)");
@@ -207,8 +280,17 @@ pub(crate) struct Tcp {
     /// Active stream sockets
     sockets: IndexMap<SocketPair, StreamSocket>,
 
-    /// TcpStream channel capacity
-    socket_capacity: usize,
+    /// TcpStream receive window, in bytes
+    window: usize,
+
+    /// Reliable delivery, if enabled
+    retransmission: Option<Retransmission>,
+
+    /// How long a connect retransmits its SYN before giving up
+    connect_timeout: Duration,
+
+    /// Acknowledgements waiting to be sent
+    acks: Vec<(SocketPair, Segment)>,
 }
 
 struct ServerSocket {
@@ -222,24 +304,71 @@ struct ServerSocket {
 }
 
 struct StreamSocket {
-    local_addr: SocketAddr,
     buf: IndexMap<u64, SequencedSegment>,
     next_send_seq: u64,
     recv_seq: u64,
-    sender: mpsc::Sender<SequencedSegment>,
+    sender: mpsc::UnboundedSender<SequencedSegment>,
     /// A simple reference counter for tracking read/write half drops. Once 0, the
     /// socket may be removed from the host.
     ref_ct: usize,
+    /// Waiting for the remote to accept the connection.
+    connecting: bool,
+
+    /// Size of the receive window, which is the same for both peers.
+    window: usize,
+    /// Total bytes sent.
+    sent: u64,
+    /// Total bytes the peer has reported as read.
+    peer_read: u64,
+    /// Total bytes read by the application.
+    read: u64,
+    /// `read` as of the last report to the peer.
+    reported_read: u64,
+    /// Total data bytes received in order.
+    received: u64,
+    /// A window update was sent while the peer's window was full. It is
+    /// resent until the peer writes again, as the peer's writes wait on it.
+    update_pending: bool,
+    /// How long to wait before resending the window update.
+    update_interval: Duration,
+    /// When the window update is next resent.
+    update_at: Option<Duration>,
+    /// Woken when the peer's window opens up, or the connection times out.
+    write_waker: Option<Waker>,
+
+    /// Sent segments that have not been acknowledged, in seq order. Only
+    /// tracked when retransmission is enabled.
+    unacked: VecDeque<(u64, SequencedSegment)>,
+    /// The last acknowledged segment, resent to probe the peer's window when
+    /// it is full.
+    probe: Option<(u64, SequencedSegment)>,
+    /// How long to wait before the next retransmission.
+    rto: Duration,
+    /// When unacknowledged segments are next retransmitted.
+    retry_at: Option<Duration>,
+    /// When the connection last made progress, while waiting on the peer.
+    waiting_since: Option<Duration>,
+    /// The connection was reset after the peer stopped responding.
+    timed_out: bool,
 }
 
 /// Stripped down version of [`Segment`] for delivery out to the application
 /// layer.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum SequencedSegment {
     Data(Bytes),
     Fin,
 }
 
+impl SequencedSegment {
+    fn to_segment(&self, seq: u64) -> Segment {
+        match self {
+            SequencedSegment::Data(data) => Segment::Data(seq, data.clone()),
+            SequencedSegment::Fin => Segment::Fin(seq),
+        }
+    }
+}
+
 impl Display for SequencedSegment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -250,20 +379,49 @@ impl Display for SequencedSegment {
 }
 
 impl StreamSocket {
-    fn new(local_addr: SocketAddr, capacity: usize) -> (Self, mpsc::Receiver<SequencedSegment>) {
-        let (tx, rx) = mpsc::channel(capacity);
+    fn new(window: usize, rto: Duration) -> (Self, mpsc::UnboundedReceiver<SequencedSegment>) {
+        let (tx, rx) = mpsc::unbounded_channel();
         let sock = Self {
-            local_addr,
             buf: IndexMap::new(),
             next_send_seq: 1,
             recv_seq: 0,
             sender: tx,
             ref_ct: 2,
+            connecting: false,
+            window,
+            sent: 0,
+            peer_read: 0,
+            read: 0,
+            reported_read: 0,
+            received: 0,
+            update_pending: false,
+            update_interval: PERSIST_INTERVAL,
+            update_at: None,
+            write_waker: None,
+            unacked: VecDeque::new(),
+            probe: None,
+            rto,
+            retry_at: None,
+            waiting_since: None,
+            timed_out: false,
         };
 
         (sock, rx)
     }
 
+    fn is_window_full(&self) -> bool {
+        self.sent - self.peer_read >= self.window as u64
+    }
+
+    // Waiting on the peer to acknowledge segments, or to open its window.
+    fn is_waiting(&self) -> bool {
+        !self.unacked.is_empty() || (self.write_waker.is_some() && self.is_window_full())
+    }
+
+    fn ack(&self) -> Segment {
+        Segment::Ack(self.recv_seq, self.read)
+    }
+
     fn assign_seq(&mut self) -> u64 {
         let seq = self.next_send_seq;
         self.next_send_seq += 1;
@@ -272,37 +430,66 @@ impl StreamSocket {
 
     // Buffer and re-order received segments by `seq` as the network may deliver
     // them out of order.
+    //
+    // The peer never sends more than the receive window allows, so the buffer
+    // is bounded without applying a capacity here.
+    //
+    // Retransmitted segments may arrive more than once, duplicates are ignored.
     fn buffer(&mut self, seq: u64, segment: SequencedSegment) -> Result<(), Protocol> {
-        use mpsc::error::TrySendError::*;
+        if seq <= self.recv_seq || self.buf.contains_key(&seq) {
+            return Ok(());
+        }
 
-        let exists = self.buf.insert(seq, segment);
+        // The peer wrote again, so it has room in the window
+        self.update_pending = false;
+        self.update_at = None;
 
-        assert!(exists.is_none(), "duplicate segment {seq}");
+        self.buf.insert(seq, segment);
 
         while self.buf.contains_key(&(self.recv_seq + 1)) {
             self.recv_seq += 1;
 
             let segment = self.buf.remove(&self.recv_seq).unwrap();
-            self.sender.try_send(segment).map_err(|e| match e {
-                Closed(_) => Protocol::Tcp(Segment::Rst),
-                Full(_) => panic!("{} socket buffer full", self.local_addr),
-            })?;
+            if let SequencedSegment::Data(data) = &segment {
+                self.received += data.len() as u64;
+            }
+            self.sender
+                .send(segment)
+                .map_err(|_| Protocol::Tcp(Segment::Rst))?;
         }
 
         Ok(())
     }
+
+    fn wake_writer(&mut self) {
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+    }
 }
 
 impl Tcp {
-    fn new(capacity: usize) -> Self {
+    fn new(
+        capacity: usize,
+        window: usize,
+        retransmission: Option<Retransmission>,
+        connect_timeout: Duration,
+    ) -> Self {
         Self {
             binds: IndexMap::new(),
             sockets: IndexMap::new(),
             server_socket_capacity: capacity,
-            socket_capacity: capacity,
+            window,
+            retransmission,
+            connect_timeout,
+            acks: vec![],
         }
     }
 
+    pub(crate) fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
     fn is_port_assigned(&self, port: u16) -> bool {
         self.binds.keys().any(|p| *p == port) || self.sockets.keys().any(|a| a.local.port() == port)
     }
@@ -327,8 +514,12 @@ impl Tcp {
         Ok(TcpListener::new(addr, notify))
     }
 
-    pub(crate) fn new_stream(&mut self, pair: SocketPair) -> mpsc::Receiver<SequencedSegment> {
-        let (sock, rx) = StreamSocket::new(pair.local, self.socket_capacity);
+    pub(crate) fn new_stream(
+        &mut self,
+        pair: SocketPair,
+    ) -> mpsc::UnboundedReceiver<SequencedSegment> {
+        let rto = self.retransmission.map(|r| r.rto).unwrap_or_default();
+        let (sock, rx) = StreamSocket::new(self.window, rto);
 
         let exists = self.sockets.insert(pair, sock);
 
@@ -344,42 +535,299 @@ impl Tcp {
     // Ideally, we could "write through" the tcp software, but this is necessary
     // due to borrowing the world to access the mut host and for sending.
     pub(crate) fn assign_send_seq(&mut self, pair: SocketPair) -> Option<u64> {
-        let sock = self.sockets.get_mut(&pair)?;
+        let sock = self.sockets.get_mut(&pair).filter(|sock| !sock.timed_out)?;
         Some(sock.assign_seq())
     }
 
+    /// Reserve room in the peer's receive window for up to `len` bytes,
+    /// assigning a send seq for them.
+    ///
+    /// Returns `Pending` while the window is full.
+    pub(crate) fn poll_reserve_send(
+        &mut self,
+        pair: SocketPair,
+        len: usize,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<(u64, usize)>> {
+        let Some(sock) = self.sockets.get_mut(&pair) else {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Broken pipe",
+            )));
+        };
+
+        if sock.timed_out {
+            return Poll::Ready(Err(timed_out()));
+        }
+
+        if sock.is_window_full() {
+            sock.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let available = sock.window as u64 - (sock.sent - sock.peer_read);
+        let len = len.min(available as usize);
+        sock.sent += len as u64;
+
+        Poll::Ready(Ok((sock.assign_seq(), len)))
+    }
+
+    /// Track a segment sent on the stream, so that it can be retransmitted
+    /// until it is acknowledged.
+    pub(crate) fn track(&mut self, pair: SocketPair, segment: &Segment) {
+        if self.retransmission.is_none() || is_same(pair.local, pair.remote) {
+            return;
+        }
+
+        let Some(sock) = self.sockets.get_mut(&pair) else {
+            return;
+        };
+
+        match segment {
+            Segment::Data(seq, data) => sock
+                .unacked
+                .push_back((*seq, SequencedSegment::Data(data.clone()))),
+            Segment::Fin(seq) => sock.unacked.push_back((*seq, SequencedSegment::Fin)),
+            _ => {}
+        }
+    }
+
+    /// Record that the application read `len` bytes from the stream.
+    ///
+    /// Returns an acknowledgement to send to the peer, once enough has been
+    /// read to be worth a window update.
+    pub(crate) fn consume(&mut self, pair: SocketPair, len: usize) -> Option<Segment> {
+        let sock = self.sockets.get_mut(&pair)?;
+        sock.read += len as u64;
+
+        if sock.read - sock.reported_read >= (sock.window as u64 / 2).max(1) {
+            // The peer's writes wait on this update if it filled the window
+            if !is_same(pair.local, pair.remote)
+                && sock.received - sock.reported_read >= sock.window as u64
+            {
+                sock.update_pending = true;
+                sock.update_interval = PERSIST_INTERVAL;
+                sock.update_at = None;
+            }
+
+            sock.reported_read = sock.read;
+            Some(sock.ack())
+        } else {
+            None
+        }
+    }
+
+    /// Mark whether the stream is waiting for the remote to accept the
+    /// connection.
+    pub(crate) fn set_connecting(&mut self, pair: SocketPair, connecting: bool) {
+        if let Some(sock) = self.sockets.get_mut(&pair) {
+            sock.connecting = connecting;
+        }
+    }
+
+    /// Whether a connect is waiting on another host to accept it. Accepting
+    /// wakes the connecting host directly, rather than by sending it a
+    /// message.
+    pub(crate) fn is_connecting(&self) -> bool {
+        self.sockets.values().any(|sock| sock.connecting)
+    }
+
+    /// Whether the connection was reset after the peer stopped responding.
+    pub(crate) fn is_timed_out(&self, pair: SocketPair) -> bool {
+        self.sockets.get(&pair).is_some_and(|sock| sock.timed_out)
+    }
+
+    /// When acknowledgements, window updates, retransmissions or timeouts are
+    /// next due, in host elapsed time.
+    fn next_retransmit(&self) -> Option<Duration> {
+        if !self.acks.is_empty() {
+            return Some(Duration::ZERO);
+        }
+
+        let updates = self
+            .sockets
+            .values()
+            .filter(|sock| sock.update_pending)
+            .map(|sock| sock.update_at.unwrap_or_default())
+            .min();
+
+        let Some(config) = self.retransmission else {
+            return updates;
+        };
+
+        let retransmits = self
+            .sockets
+            .values()
+            .filter(|sock| !sock.timed_out && sock.is_waiting())
+            .map(|sock| {
+                let retry_at = sock.retry_at.unwrap_or_default();
+                let timeout_at = sock
+                    .waiting_since
+                    .map(|since| since + config.timeout)
+                    .unwrap_or_default();
+
+                retry_at.min(timeout_at)
+            })
+            .min();
+
+        updates.into_iter().chain(retransmits).min()
+    }
+
+    /// Return acknowledgements waiting to be sent, along with window updates
+    /// and segments that are due to be retransmitted at `now`.
+    ///
+    /// Connections that have not made progress within the retransmission
+    /// timeout are reset.
+    fn poll_retransmit(&mut self, now: Duration) -> Vec<(SocketPair, Segment)> {
+        let mut segments = std::mem::take(&mut self.acks);
+
+        // Window updates are resent whether or not retransmission is enabled,
+        // as a lost one would leave the peer's writes waiting forever
+        for (pair, sock) in &mut self.sockets {
+            if !sock.update_pending {
+                continue;
+            }
+
+            let update_at = *sock.update_at.get_or_insert(now + sock.update_interval);
+            if now >= update_at {
+                segments.push((*pair, sock.ack()));
+                sock.update_interval = (sock.update_interval * 2).min(MAX_PERSIST_INTERVAL);
+                sock.update_at = Some(now + sock.update_interval);
+            }
+        }
+
+        let Some(config) = self.retransmission else {
+            return segments;
+        };
+
+        for (pair, sock) in &mut self.sockets {
+            let is_waiting = sock.is_waiting();
+
+            if !is_waiting {
+                sock.retry_at = None;
+                sock.waiting_since = None;
+            }
+
+            if sock.timed_out || !is_waiting {
+                continue;
+            }
+
+            let since = *sock.waiting_since.get_or_insert(now);
+            if now - since >= config.timeout {
+                tracing::trace!(target: TRACING_TARGET, ?pair, "Timed out");
+
+                sock.timed_out = true;
+                sock.buf.clear();
+                sock.unacked.clear();
+                // Dropping the sender fails reads, once they have drained
+                sock.sender = mpsc::unbounded_channel().0;
+                sock.wake_writer();
+
+                segments.push((*pair, Segment::Rst));
+                continue;
+            }
+
+            let retry_at = *sock.retry_at.get_or_insert(now + sock.rto);
+            if now < retry_at {
+                continue;
+            }
+
+            if sock.unacked.is_empty() {
+                segments.extend(
+                    sock.probe
+                        .iter()
+                        .map(|(seq, s)| (*pair, s.to_segment(*seq))),
+                );
+            } else {
+                segments.extend(
+                    sock.unacked
+                        .iter()
+                        .map(|(seq, s)| (*pair, s.to_segment(*seq))),
+                );
+            }
+
+            sock.rto *= 2;
+            sock.retry_at = Some(now + sock.rto);
+        }
+
+        segments
+    }
+
     fn receive_from_network(
         &mut self,
         src: SocketAddr,
         dst: SocketAddr,
         segment: Segment,
     ) -> Result<(), Protocol> {
+        let pair = SocketPair::new(dst, src);
+
         match segment {
             Segment::Syn(syn) => {
-                // If bound, queue the syn; else we drop the syn triggering
+                // Retransmitted syns for a connection that is already
+                // established are ignored.
+                if self.sockets.contains_key(&pair) {
+                    return Ok(());
+                }
+
+                // If bound, queue the syn; else respond with a RST triggering
                 // connection refused on the client.
-                if let Some(b) = self.binds.get_mut(&dst.port()) {
-                    if b.deque.len() == self.server_socket_capacity {
-                        panic!("{} server socket buffer full", dst);
-                    }
+                match self.binds.get_mut(&dst.port()) {
+                    Some(b) if matches(b.bind_addr, dst) => {
+                        if b.deque.iter().any(|(_, origin)| *origin == src) {
+                            return Ok(());
+                        }
+
+                        if b.deque.len() == self.server_socket_capacity {
+                            panic!("{} server socket buffer full", dst);
+                        }
 
-                    if matches(b.bind_addr, dst) {
                         b.deque.push_back((syn, src));
                         b.notify.notify_one();
                     }
+                    _ => return Err(Protocol::Tcp(Segment::Rst)),
                 }
             }
-            Segment::Data(seq, data) => match self.sockets.get_mut(&SocketPair::new(dst, src)) {
-                Some(sock) => sock.buffer(seq, SequencedSegment::Data(data))?,
+            Segment::Data(seq, data) => match self.sockets.get_mut(&pair) {
+                Some(sock) => {
+                    sock.buffer(seq, SequencedSegment::Data(data))?;
+                    if self.retransmission.is_some() {
+                        self.acks.push((pair, sock.ack()));
+                    }
+                }
                 None => return Err(Protocol::Tcp(Segment::Rst)),
             },
-            Segment::Fin(seq) => match self.sockets.get_mut(&SocketPair::new(dst, src)) {
-                Some(sock) => sock.buffer(seq, SequencedSegment::Fin)?,
+            Segment::Fin(seq) => match self.sockets.get_mut(&pair) {
+                Some(sock) => {
+                    sock.buffer(seq, SequencedSegment::Fin)?;
+                    if self.retransmission.is_some() {
+                        self.acks.push((pair, sock.ack()));
+                    }
+                }
                 None => return Err(Protocol::Tcp(Segment::Rst)),
             },
+            Segment::Ack(seq, read) => {
+                if let Some(sock) = self.sockets.get_mut(&pair) {
+                    // The peer is alive, even if its window is still full
+                    sock.waiting_since = None;
+
+                    let mut progress = read > sock.peer_read;
+                    sock.peer_read = sock.peer_read.max(read);
+
+                    while sock.unacked.front().is_some_and(|(s, _)| *s <= seq) {
+                        sock.probe = sock.unacked.pop_front();
+                        progress = true;
+                    }
+
+                    if progress {
+                        sock.rto = self.retransmission.map(|r| r.rto).unwrap_or_default();
+                        sock.retry_at = None;
+                        sock.wake_writer();
+                    }
+                }
+            }
             Segment::Rst => {
-                if self.sockets.get(&SocketPair::new(dst, src)).is_some() {
-                    self.sockets.remove(&SocketPair::new(dst, src)).unwrap();
+                if let Some(mut sock) = self.sockets.remove(&pair) {
+                    sock.wake_writer();
                 }
             }
         };
@@ -387,6 +835,11 @@ impl Tcp {
         Ok(())
     }
 
+    /// Remove the stream for a connect that failed.
+    pub(crate) fn remove_stream(&mut self, pair: SocketPair) {
+        self.sockets.remove(&pair);
+    }
+
     pub(crate) fn close_stream_half(&mut self, pair: SocketPair) {
         // Receiving a RST removes the socket, so it's possible that has occured
         // when halfs of the stream drop.
@@ -423,13 +876,27 @@ pub(crate) fn is_same(src: SocketAddr, dst: SocketAddr) -> bool {
     dst.ip().is_loopback() || src.ip() == dst.ip()
 }
 
+/// The error for a connection reset by the retransmission timeout.
+pub(crate) fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "Connection timed out")
+}
+
 #[cfg(test)]
 mod test {
     use crate::{Host, Result};
+    use std::time::Duration;
 
     #[test]
     fn recycle_ports() -> Result {
-        let mut host = Host::new(std::net::Ipv4Addr::UNSPECIFIED.into(), 1, 1);
+        let mut host = Host::new(
+            std::net::Ipv4Addr::UNSPECIFIED.into(),
+            Duration::ZERO,
+            1,
+            1,
+            None,
+            Duration::ZERO,
+            1,
+        );
 
         host.udp.bind((host.addr, 65534).into())?;
         host.udp.bind((host.addr, 65535).into())?;
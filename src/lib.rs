@@ -57,6 +57,15 @@
 //!   available for introspection using [`Sim`]'s `links` method.
 //! * [`release`], which releases all "in flight" messages between hosts
 //!
+//! Each has a `_oneway` variant, such as [`partition_oneway`], which only
+//! affects messages sent in one direction.
+//!
+//! Faults can also be scheduled ahead of time at specific simulated times with
+//! a [`FaultSchedule`], or injected at random by a [`Nemesis`].
+//!
+//! Individual messages can be dropped, delayed, held, duplicated or rewritten
+//! with [`Sim::set_network_filter`].
+//!
 //! # Tracing
 //!
 //! The `tracing` crate is used to emit important events during the lifetime of
@@ -64,13 +73,20 @@
 //!
 //! This can be configured using `RUST_LOG=turmoil=info`.
 //!
+//! For a structured record of what happened, enable
+//! [`Builder::record_trace`] and inspect [`Sim::trace`]. See the [`trace`]
+//! module for details.
+//!
 //! # Feature flags
 //!
 //! * `regex`: Enables regex host resolution through `ToIpAddrs`
 //!
 //! ## tokio_unstable
 //!
-//! Turmoil uses [unhandled_panic] to forward host panics as test failures. See
+//! Turmoil uses [unhandled_panic] to forward host panics as test failures, and
+//! seeds tokio's randomness, such as the order `select!` polls its branches in,
+//! from the simulation seed. Without it, simulations are only reproducible from
+//! their seed if host software does not rely on tokio's randomness. See
 //! [unstable features] to opt in.
 //!
 //! [unhandled_panic]:
@@ -86,38 +102,54 @@ use std::net::IpAddr;
 
 pub use builder::Builder;
 
+mod clock;
+pub use clock::ClockSkew;
+
 mod config;
 use config::Config;
+pub use config::{BurstLoss, Corruption, CorruptionKind, LatencyDistribution, Retransmission};
 
 mod dns;
 use dns::Dns;
 pub use dns::{ToIpAddr, ToIpAddrs, ToSocketAddrs};
 
 mod envelope;
-use envelope::Envelope;
-pub use envelope::{Datagram, Protocol, Segment};
+pub use envelope::{Datagram, Envelope, Protocol, Segment};
 
 mod error;
 pub use error::Result;
 
 mod host;
-pub use host::elapsed;
 use host::Host;
+pub use host::{elapsed, now};
 
 mod ip;
 pub use ip::IpVersion;
 
 pub mod net;
 
+mod nemesis;
+pub use nemesis::Nemesis;
+
+mod pcap;
+
 mod rt;
 use rt::Rt;
 
+mod schedule;
+pub use schedule::FaultSchedule;
+
 mod sim;
 pub use sim::Sim;
 
+mod stats;
+pub use stats::{Histogram, HostStats, LinkStats, Stats};
+
 mod top;
 use top::Topology;
-pub use top::{LinkIter, LinksIter, SentRef};
+pub use top::{LinkIter, LinkState, LinksIter, SentRef, Verdict};
+
+pub mod trace;
 
 mod world;
 use world::World;
@@ -188,3 +220,36 @@ pub fn partition(a: impl ToIpAddrs, b: impl ToIpAddrs) {
 pub fn repair(a: impl ToIpAddrs, b: impl ToIpAddrs) {
     World::current(|world| world.repair_many(a, b))
 }
+
+/// Hold messages sent from one host, or set of hosts, to another until
+/// [`release_oneway`] is called. Messages sent in the other direction are
+/// unaffected.
+///
+/// Must be called from within a Turmoil simulation.
+pub fn hold_oneway(from: impl ToIpAddrs, to: impl ToIpAddrs) {
+    World::current(|world| world.hold_oneway_many(from, to))
+}
+
+/// The opposite of [`hold_oneway`]. Held messages sent from one host, or set of
+/// hosts, to another are immediately delivered.
+///
+/// Must be called from within a Turmoil simulation.
+pub fn release_oneway(from: impl ToIpAddrs, to: impl ToIpAddrs) {
+    World::current(|world| world.release_oneway_many(from, to))
+}
+
+/// Partition messages sent from one host, or set of hosts, to another.
+/// Messages sent in the other direction are unaffected.
+///
+/// Must be called from within a Turmoil simulation.
+pub fn partition_oneway(from: impl ToIpAddrs, to: impl ToIpAddrs) {
+    World::current(|world| world.partition_oneway_many(from, to))
+}
+
+/// Repair messages sent from one host, or set of hosts, to another. The
+/// opposite of [`partition_oneway`].
+///
+/// Must be called from within a Turmoil simulation.
+pub fn repair_oneway(from: impl ToIpAddrs, to: impl ToIpAddrs) {
+    World::current(|world| world.repair_oneway_many(from, to))
+}
@@ -0,0 +1,299 @@
+use crate::FaultSchedule;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use std::net::IpAddr;
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// Randomly injects faults into the simulation.
+///
+/// Every decision the nemesis makes, which fault to inject, which hosts are
+/// affected and for how long, is driven by the simulation's rng, so a chaos run
+/// is reproducible from its seed.
+///
+/// Each kind of fault is considered once per [`Sim::step`], and at most one
+/// fault of each kind is active at a time. Only hosts registered with
+/// [`Sim::host`] are affected; clients are left alone.
+///
+/// ```
+/// use std::time::Duration;
+/// use turmoil::Nemesis;
+///
+/// let mut nemesis = Nemesis::new();
+/// nemesis
+///     .crash(0.001, Duration::from_millis(100)..=Duration::from_secs(1))
+///     .partition(0.001, Duration::from_millis(500)..=Duration::from_secs(2));
+///
+/// let mut sim = turmoil::Builder::new().nemesis(nemesis).build();
+/// ```
+///
+/// [`Sim::step`]: crate::Sim::step
+/// [`Sim::host`]: crate::Sim::host
+#[derive(Clone, Default)]
+pub struct Nemesis {
+    crash: Option<Chaos>,
+    partition: Option<Chaos>,
+    hold: Option<Chaos>,
+}
+
+/// How often a fault is injected, and how long it lasts.
+#[derive(Clone)]
+struct Chaos {
+    probability: f64,
+    duration: RangeInclusive<Duration>,
+}
+
+impl Nemesis {
+    /// Create a nemesis that injects no faults. Enable each kind of fault with
+    /// [`crash`](Nemesis::crash), [`partition`](Nemesis::partition) and
+    /// [`hold`](Nemesis::hold).
+    pub fn new() -> Nemesis {
+        Nemesis::default()
+    }
+
+    /// Crash a random host with `probability` each step, restarting it after a
+    /// random duration within `downtime`.
+    pub fn crash(&mut self, probability: f64, downtime: RangeInclusive<Duration>) -> &mut Self {
+        self.crash = Some(Chaos::new(probability, downtime));
+        self
+    }
+
+    /// Split the hosts into two random groups with `probability` each step,
+    /// repairing the partition after a random duration within `duration`.
+    pub fn partition(&mut self, probability: f64, duration: RangeInclusive<Duration>) -> &mut Self {
+        self.partition = Some(Chaos::new(probability, duration));
+        self
+    }
+
+    /// Hold messages on the link between two random hosts with `probability`
+    /// each step, releasing them after a random duration within `duration`.
+    ///
+    /// Partitions and holds never overlap on a link: the held hosts are on the
+    /// same side of any active partition.
+    pub fn hold(&mut self, probability: f64, duration: RangeInclusive<Duration>) -> &mut Self {
+        self.hold = Some(Chaos::new(probability, duration));
+        self
+    }
+}
+
+impl Chaos {
+    fn new(probability: f64, duration: RangeInclusive<Duration>) -> Chaos {
+        assert!(
+            (0.0..=1.0).contains(&probability),
+            "probability must be between 0 and 1, got {probability}"
+        );
+        assert!(!duration.is_empty(), "duration range must not be empty");
+
+        Chaos {
+            probability,
+            duration,
+        }
+    }
+
+    /// Roll for the fault, returning how long it lasts if it is injected.
+    fn roll(&self, rng: &mut dyn RngCore) -> Option<Duration> {
+        rng.gen_bool(self.probability)
+            .then(|| rng.gen_range(self.duration.clone()))
+    }
+}
+
+/// Tracks the faults injected by a [`Nemesis`].
+pub(crate) struct Runner {
+    nemesis: Nemesis,
+
+    /// When the active crash, partition and hold end.
+    crash_until: Duration,
+    partition_until: Duration,
+    hold_until: Duration,
+
+    /// The sides of the active partition.
+    sides: Vec<Vec<IpAddr>>,
+
+    /// The hosts whose link is held.
+    held: Option<(IpAddr, IpAddr)>,
+}
+
+impl Runner {
+    pub(crate) fn new(nemesis: Nemesis) -> Runner {
+        Runner {
+            nemesis,
+            crash_until: Duration::ZERO,
+            partition_until: Duration::ZERO,
+            hold_until: Duration::ZERO,
+            sides: vec![],
+            held: None,
+        }
+    }
+
+    /// Whether any faults are injected. The nemesis rolls for faults every
+    /// step, so idle time is not skipped while it is enabled.
+    pub(crate) fn is_enabled(&self) -> bool {
+        let Nemesis {
+            crash,
+            partition,
+            hold,
+        } = &self.nemesis;
+
+        crash.is_some() || partition.is_some() || hold.is_some()
+    }
+
+    /// Randomly choose faults to inject at `now`, scheduling them, and their
+    /// repair, in `faults`.
+    ///
+    /// `hosts` are the hosts that may be affected, along with whether their
+    /// software is running.
+    ///
+    /// A link has a single state, so a partition and a hold on the same link
+    /// would undo each other: holding replaces the partition, and repairing or
+    /// releasing heals both. The held pair is kept on one side of the
+    /// partition instead.
+    pub(crate) fn step(
+        &mut self,
+        now: Duration,
+        hosts: &[(IpAddr, bool)],
+        rng: &mut dyn RngCore,
+        faults: &mut FaultSchedule,
+    ) {
+        if let Some(chaos) = self
+            .nemesis
+            .crash
+            .as_ref()
+            .filter(|_| now >= self.crash_until)
+        {
+            let running = hosts
+                .iter()
+                .filter(|(_, running)| *running)
+                .map(|(addr, _)| *addr)
+                .collect::<Vec<_>>();
+
+            if !running.is_empty() {
+                if let Some(downtime) = chaos.roll(rng) {
+                    let addr = *running.choose(rng).unwrap();
+
+                    faults.crash(now, addr).bounce(now + downtime, addr);
+                    self.crash_until = now + downtime;
+                }
+            }
+        }
+
+        if hosts.len() < 2 {
+            return;
+        }
+
+        let addrs = hosts.iter().map(|(addr, _)| *addr).collect::<Vec<_>>();
+
+        if now >= self.partition_until {
+            self.sides.clear();
+        }
+
+        if now >= self.hold_until {
+            self.held = None;
+        }
+
+        if let Some(chaos) = self
+            .nemesis
+            .partition
+            .as_ref()
+            .filter(|_| now >= self.partition_until)
+        {
+            // The second held host joins the first one's side
+            let mut addrs = addrs.clone();
+            if let Some((_, b)) = self.held {
+                addrs.retain(|&addr| addr != b);
+            }
+
+            if addrs.len() >= 2 {
+                if let Some(duration) = chaos.roll(rng) {
+                    addrs.shuffle(rng);
+                    let (a, b) = addrs.split_at(rng.gen_range(1..addrs.len()));
+                    let mut sides = vec![a.to_vec(), b.to_vec()];
+
+                    if let Some((a, b)) = self.held {
+                        let side = sides.iter_mut().find(|side| side.contains(&a));
+                        side.expect("held host is partitioned").push(b);
+                    }
+
+                    for &a in &sides[0] {
+                        for &b in &sides[1] {
+                            faults.partition(now, a, b).repair(now + duration, a, b);
+                        }
+                    }
+                    self.partition_until = now + duration;
+                    self.sides = sides;
+                }
+            }
+        }
+
+        if let Some(chaos) = self
+            .nemesis
+            .hold
+            .as_ref()
+            .filter(|_| now >= self.hold_until)
+        {
+            // Pick from one side of an active partition
+            let sides = match &self.sides[..] {
+                [] => vec![addrs],
+                sides => sides
+                    .iter()
+                    .filter(|side| side.len() >= 2)
+                    .cloned()
+                    .collect(),
+            };
+
+            if !sides.is_empty() {
+                if let Some(duration) = chaos.roll(rng) {
+                    let side = sides.choose(rng).unwrap();
+                    let mut pair = side.choose_multiple(rng, 2);
+                    let (a, b) = (*pair.next().unwrap(), *pair.next().unwrap());
+
+                    faults.hold(now, a, b).release(now + duration, a, b);
+                    self.hold_until = now + duration;
+                    self.held = Some((a, b));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Nemesis, Runner};
+    use crate::FaultSchedule;
+
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::Duration;
+
+    #[test]
+    fn holds_do_not_cross_partitions() {
+        let mut nemesis = Nemesis::new();
+        nemesis
+            .partition(0.1, Duration::from_millis(1)..=Duration::from_millis(20))
+            .hold(0.1, Duration::from_millis(1)..=Duration::from_millis(20));
+
+        let hosts = (1..=4)
+            .map(|i| (IpAddr::from(Ipv4Addr::new(192, 168, 0, i)), true))
+            .collect::<Vec<_>>();
+
+        let mut runner = Runner::new(nemesis);
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut faults = FaultSchedule::new();
+        let mut overlapped = 0;
+
+        for ms in 0..10_000 {
+            runner.step(Duration::from_millis(ms), &hosts, &mut rng, &mut faults);
+
+            if let (Some((a, b)), false) = (runner.held, runner.sides.is_empty()) {
+                assert!(runner
+                    .sides
+                    .iter()
+                    .any(|s| s.contains(&a) && s.contains(&b)));
+                overlapped += 1;
+            }
+        }
+
+        assert!(overlapped > 0);
+    }
+}
@@ -91,6 +91,10 @@ impl TcpListener {
                 let pair = SocketPair::new(my_addr, origin);
                 let rx = host.tcp.new_stream(pair);
 
+                if let Some(origin) = world.hosts.get_mut(&origin.ip()) {
+                    origin.woken = true;
+                }
+
                 Some((TcpStream::new(pair, rx), origin))
             });
 
@@ -11,13 +11,14 @@ use std::{
 use bytes::{Buf, Bytes};
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
-    sync::{mpsc, oneshot},
-    time::sleep,
+    sync::mpsc,
+    time::{sleep, sleep_until, Instant},
 };
 
 use crate::{
     envelope::{Envelope, Protocol, Segment, Syn},
     host::is_same,
+    host::timed_out,
     host::SequencedSegment,
     net::SocketPair,
     world::World,
@@ -26,6 +27,10 @@ use crate::{
 
 use super::split_owned::{OwnedReadHalf, OwnedWriteHalf};
 
+/// How long to wait before retransmitting the first SYN, doubling for each
+/// retransmission.
+const SYN_RTO: Duration = Duration::from_secs(1);
+
 /// A simulated TCP stream between a local and a remote socket.
 ///
 /// All methods must be called from a host within a Turmoil simulation.
@@ -36,7 +41,10 @@ pub struct TcpStream {
 }
 
 impl TcpStream {
-    pub(crate) fn new(pair: SocketPair, receiver: mpsc::Receiver<SequencedSegment>) -> Self {
+    pub(crate) fn new(
+        pair: SocketPair,
+        receiver: mpsc::UnboundedReceiver<SequencedSegment>,
+    ) -> Self {
         let pair = Arc::new(pair);
         let read_half = ReadHalf {
             pair: pair.clone(),
@@ -59,10 +67,15 @@ impl TcpStream {
     }
 
     /// Opens a TCP connection to a remote host.
+    ///
+    /// The SYN is retransmitted with exponential backoff until the connection
+    /// is accepted, failing with [`io::ErrorKind::TimedOut`] once the connect
+    /// timeout elapses. If the remote host is not listening the connection is
+    /// refused.
     pub async fn connect<A: ToSocketAddrs>(addr: A) -> Result<TcpStream> {
-        let (ack, syn_ack) = oneshot::channel();
+        let (ack, mut syn_ack) = mpsc::unbounded_channel();
 
-        let (pair, rx) = World::current(|world| {
+        let (pair, mut rx, timeout) = World::current(|world| {
             let dst = addr.to_socket_addr(&world.dns);
 
             let host = world.current_host_mut();
@@ -73,20 +86,52 @@ impl TcpStream {
 
             let pair = SocketPair::new(local_addr, dst);
             let rx = host.tcp.new_stream(pair);
+            host.tcp.set_connecting(pair, true);
+            let timeout = host.tcp.connect_timeout();
 
-            let syn = Protocol::Tcp(Segment::Syn(Syn { ack }));
-            if !is_same(local_addr, dst) {
-                world.send_message(local_addr, dst, syn)?;
-            } else {
-                send_loopback(local_addr, dst, syn);
-            };
-
-            Ok::<_, Error>((pair, rx))
+            Ok::<_, Error>((pair, rx, timeout))
         })?;
 
-        syn_ack.await.map_err(|_| {
-            io::Error::new(io::ErrorKind::ConnectionRefused, pair.remote.to_string())
-        })?;
+        let deadline = Instant::now() + timeout;
+        let mut rto = SYN_RTO;
+
+        let res = loop {
+            let sent =
+                World::current(|world| send(world, pair, Segment::Syn(Syn { ack: ack.clone() })));
+            if let Err(e) = sent {
+                break Err(e);
+            }
+
+            // The stream's channel closes if the remote responds with a RST.
+            // Nothing is sent on it before the connection is accepted.
+            tokio::select! {
+                biased;
+                _ = syn_ack.recv() => break Ok(()),
+                None = rx.recv() => {
+                    break Err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        pair.remote.to_string(),
+                    ));
+                }
+                _ = sleep_until(deadline.min(Instant::now() + rto)) => {}
+            }
+
+            if Instant::now() >= deadline {
+                break Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    pair.remote.to_string(),
+                ));
+            }
+
+            rto *= 2;
+        };
+
+        if let Err(e) = res {
+            World::current(|world| world.current_host_mut().tcp.remove_stream(pair));
+            return Err(e);
+        }
+
+        World::current(|world| world.current_host_mut().tcp.set_connecting(pair, false));
 
         tracing::trace!(target: TRACING_TARGET, src = ?pair.remote, dst = ?pair.local, protocol = %"TCP SYN-ACK", "Recv");
 
@@ -137,7 +182,7 @@ pub(crate) struct ReadHalf {
 }
 
 struct Rx {
-    recv: mpsc::Receiver<SequencedSegment>,
+    recv: mpsc::UnboundedReceiver<SequencedSegment>,
     /// The remaining bytes of a received data segment.
     ///
     /// This is used to support read impls by stashing available bytes for
@@ -152,7 +197,7 @@ impl ReadHalf {
         }
 
         if let Some(bytes) = self.rx.buffer.take() {
-            self.rx.buffer = Self::put_slice(bytes, buf);
+            self.rx.buffer = self.put_slice(bytes, buf);
 
             return Poll::Ready(Ok(()));
         }
@@ -163,7 +208,7 @@ impl ReadHalf {
 
                 match seg {
                     SequencedSegment::Data(bytes) => {
-                        self.rx.buffer = Self::put_slice(bytes, buf);
+                        self.rx.buffer = self.put_slice(bytes, buf);
                     }
                     SequencedSegment::Fin => {
                         self.is_closed = true;
@@ -172,24 +217,38 @@ impl ReadHalf {
 
                 Poll::Ready(Ok(()))
             }
-            None => Poll::Ready(Err(io::Error::new(
-                io::ErrorKind::ConnectionReset,
-                "Connection reset",
-            ))),
+            None => {
+                let is_timed_out =
+                    World::current(|world| world.current_host_mut().tcp.is_timed_out(*self.pair));
+
+                Poll::Ready(Err(if is_timed_out {
+                    timed_out()
+                } else {
+                    io::Error::new(io::ErrorKind::ConnectionReset, "Connection reset")
+                }))
+            }
         }
     }
 
     /// Put bytes in `buf` based on the minimum of `avail` and its remaining
-    /// capacity.
+    /// capacity, opening the peer's window by the amount read.
     ///
     /// Returns an optional `Bytes` containing any remainder of `avail` that was
     /// not consumed.
-    fn put_slice(mut avail: Bytes, buf: &mut ReadBuf) -> Option<Bytes> {
+    fn put_slice(&self, mut avail: Bytes, buf: &mut ReadBuf) -> Option<Bytes> {
         let amt = std::cmp::min(avail.len(), buf.remaining());
 
         buf.put_slice(&avail[..amt]);
         avail.advance(amt);
 
+        World::current(|world| {
+            let pair = *self.pair;
+            if let Some(ack) = world.current_host_mut().tcp.consume(pair, amt) {
+                // The window update is lost if the peer is unreachable
+                let _ = send(world, pair, ack);
+            }
+        });
+
         if avail.is_empty() {
             None
         } else {
@@ -214,7 +273,8 @@ pub(crate) struct WriteHalf {
 }
 
 impl WriteHalf {
-    fn poll_write_priv(&self, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
+    // Writes wait for room in the peer's receive window, and may be partial.
+    fn poll_write_priv(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize>> {
         if buf.remaining() == 0 {
             return Poll::Ready(Ok(0));
         }
@@ -226,17 +286,21 @@ impl WriteHalf {
             )));
         }
 
-        let res = World::current(|world| {
-            let bytes = Bytes::copy_from_slice(buf);
-            let len = bytes.len();
-
-            let seq = self.seq(world)?;
-            self.send(world, Segment::Data(seq, bytes))?;
+        World::current(|world| {
+            let reserved =
+                world
+                    .current_host_mut()
+                    .tcp
+                    .poll_reserve_send(*self.pair, buf.len(), cx);
 
-            Ok(len)
-        });
+            let (seq, len) = match ready!(reserved) {
+                Ok(reserved) => reserved,
+                Err(e) => return Poll::Ready(Err(e)),
+            };
 
-        Poll::Ready(res)
+            let bytes = Bytes::copy_from_slice(&buf[..len]);
+            Poll::Ready(self.send(world, Segment::Data(seq, bytes)).map(|_| len))
+        })
     }
 
     fn poll_shutdown_priv(&mut self) -> Poll<Result<()>> {
@@ -269,15 +333,21 @@ impl WriteHalf {
             .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "Broken pipe"))
     }
 
+    // Sent segments are tracked for retransmission, if enabled.
     fn send(&self, world: &mut World, segment: Segment) -> Result<()> {
-        let message = Protocol::Tcp(segment);
-        if is_same(self.pair.local, self.pair.remote) {
-            send_loopback(self.pair.local, self.pair.remote, message);
-        } else {
-            world.send_message(self.pair.local, self.pair.remote, message)?;
-        }
-        Ok(())
+        world.current_host_mut().tcp.track(*self.pair, &segment);
+        send(world, *self.pair, segment)
+    }
+}
+
+fn send(world: &mut World, pair: SocketPair, segment: Segment) -> Result<()> {
+    let message = Protocol::Tcp(segment);
+    if is_same(pair.local, pair.remote) {
+        send_loopback(pair.local, pair.remote, message);
+    } else {
+        world.send_message(pair.local, pair.remote, message)?;
     }
+    Ok(())
 }
 
 fn send_loopback(src: SocketAddr, dst: SocketAddr, message: Protocol) {
@@ -380,8 +450,8 @@ impl Drop for WriteHalf {
 
             if let Some(seq) = world.current_host_mut().tcp.assign_send_seq(pair) {
                 let _ = self.send(world, Segment::Fin(seq));
-                world.current_host_mut().tcp.close_stream_half(pair);
             }
+            world.current_host_mut().tcp.close_stream_half(pair);
         })
     }
 }
@@ -0,0 +1,349 @@
+use crate::envelope::{Protocol, Segment};
+
+use indexmap::IndexMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+/// Raw IPv4/IPv6 packets, without a link layer header.
+const LINKTYPE_RAW: u32 = 101;
+
+const SNAPLEN: u32 = 65535;
+
+const PROTO_TCP: u8 = 6;
+const PROTO_UDP: u8 = 17;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// Writes delivered messages to a pcap file, synthesizing IP and TCP/UDP
+/// headers so the capture can be inspected with standard tooling.
+pub(crate) struct Pcap {
+    out: BufWriter<File>,
+
+    /// Offset of simulated time from the unix epoch, used for timestamps.
+    since_epoch: Duration,
+
+    /// Per connection TCP sequence numbers.
+    flows: IndexMap<(SocketAddr, SocketAddr), Flow>,
+}
+
+/// Turmoil numbers TCP segments rather than bytes, so byte offsets are assigned
+/// as segments are sent and looked up when they are delivered. They are
+/// forgotten once the stream is closed by a FIN or RST.
+#[derive(Default)]
+struct Flow {
+    next_seq: u32,
+    seqs: IndexMap<u64, u32>,
+}
+
+impl Pcap {
+    pub(crate) fn create(path: &Path, since_epoch: Duration) -> io::Result<Pcap> {
+        let mut out = BufWriter::new(File::create(path)?);
+
+        out.write_all(&0xa1b2c3d4u32.to_le_bytes())?;
+        out.write_all(&2u16.to_le_bytes())?;
+        out.write_all(&4u16.to_le_bytes())?;
+        out.write_all(&0i32.to_le_bytes())?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(&SNAPLEN.to_le_bytes())?;
+        out.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+
+        Ok(Pcap {
+            out,
+            since_epoch,
+            flows: IndexMap::new(),
+        })
+    }
+
+    /// Track a message being sent, assigning TCP sequence numbers.
+    pub(crate) fn send(&mut self, src: SocketAddr, dst: SocketAddr, protocol: &Protocol) {
+        let (seq, len) = match protocol {
+            Protocol::Tcp(Segment::Data(seq, data)) => (*seq, data.len() as u32),
+            Protocol::Tcp(Segment::Fin(seq)) => (*seq, 1),
+            _ => return,
+        };
+
+        // The SYN consumes the first sequence number
+        let flow = self.flows.entry((src, dst)).or_insert_with(|| Flow {
+            next_seq: 1,
+            ..Default::default()
+        });
+
+        // Retransmitted segments keep their original sequence number
+        if flow.seqs.contains_key(&seq) {
+            return;
+        }
+
+        flow.seqs.insert(seq, flow.next_seq);
+        flow.next_seq = flow.next_seq.wrapping_add(len);
+    }
+
+    /// Write a delivered message to the capture.
+    pub(crate) fn deliver(
+        &mut self,
+        elapsed: Duration,
+        src: SocketAddr,
+        dst: SocketAddr,
+        protocol: &Protocol,
+    ) -> io::Result<()> {
+        let (proto, mut l4) = match protocol {
+            Protocol::Tcp(segment) => (PROTO_TCP, self.tcp(src, dst, segment)),
+            Protocol::Udp(datagram) => (PROTO_UDP, udp(src, dst, &datagram.0)),
+        };
+
+        // A closed stream's sequence numbers are no longer needed
+        match protocol {
+            Protocol::Tcp(Segment::Fin(_)) => {
+                self.flows.swap_remove(&(src, dst));
+            }
+            Protocol::Tcp(Segment::Rst) => {
+                self.flows.swap_remove(&(src, dst));
+                self.flows.swap_remove(&(dst, src));
+            }
+            _ => {}
+        }
+
+        // Headers describe the whole packet, even if the capture is truncated
+        checksum_l4(src.ip(), dst.ip(), proto, &mut l4);
+
+        let mut packet = ip(src.ip(), dst.ip(), proto, l4.len());
+        packet.extend_from_slice(&l4);
+
+        let orig_len = packet.len();
+        packet.truncate(SNAPLEN as usize);
+
+        let ts = self.since_epoch + elapsed;
+        self.out.write_all(&(ts.as_secs() as u32).to_le_bytes())?;
+        self.out.write_all(&ts.subsec_micros().to_le_bytes())?;
+        self.out.write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.out.write_all(&(orig_len as u32).to_le_bytes())?;
+        self.out.write_all(&packet)
+    }
+
+    fn tcp(&mut self, src: SocketAddr, dst: SocketAddr, segment: &Segment) -> Vec<u8> {
+        let mut seq = |s| {
+            self.flows
+                .get_mut(&(src, dst))
+                .and_then(|flow| flow.seqs.get(&s).copied())
+                .unwrap_or_default()
+        };
+
+        let (seq, flags, payload): (u32, u8, &[u8]) = match segment {
+            Segment::Syn(_) => (0, TCP_SYN, &[]),
+            Segment::Data(s, data) => (seq(*s), TCP_PSH, data),
+            Segment::Fin(s) => (seq(*s), TCP_FIN, &[]),
+            Segment::Ack(..) => (0, TCP_ACK, &[]),
+            Segment::Rst => (0, TCP_RST, &[]),
+        };
+
+        let mut buf = Vec::with_capacity(20 + payload.len());
+        buf.extend_from_slice(&src.port().to_be_bytes());
+        buf.extend_from_slice(&dst.port().to_be_bytes());
+        buf.extend_from_slice(&seq.to_be_bytes());
+        // ack number
+        buf.extend_from_slice(&0u32.to_be_bytes());
+        // data offset (5 words), no options
+        buf.push(5 << 4);
+        buf.push(flags);
+        // window
+        buf.extend_from_slice(&u16::MAX.to_be_bytes());
+        // checksum, urgent pointer
+        buf.extend_from_slice(&[0; 4]);
+        buf.extend_from_slice(payload);
+        buf
+    }
+}
+
+impl Drop for Pcap {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+/// A length for a 16 bit header field, clamped for oversized packets.
+fn len16(len: usize) -> u16 {
+    len.min(u16::MAX as usize) as u16
+}
+
+fn udp(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let len = len16(8 + payload.len());
+
+    let mut buf = Vec::with_capacity(8 + payload.len());
+    buf.extend_from_slice(&src.port().to_be_bytes());
+    buf.extend_from_slice(&dst.port().to_be_bytes());
+    buf.extend_from_slice(&len.to_be_bytes());
+    // checksum
+    buf.extend_from_slice(&[0; 2]);
+    buf.extend_from_slice(payload);
+    buf
+}
+
+fn ip_header_len(addr: IpAddr) -> usize {
+    match addr {
+        IpAddr::V4(_) => 20,
+        IpAddr::V6(_) => 40,
+    }
+}
+
+fn ip(src: IpAddr, dst: IpAddr, proto: u8, payload_len: usize) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(ip_header_len(src) + payload_len);
+
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let total_len = len16(20 + payload_len);
+
+            // version 4, header length 5 words; dscp/ecn
+            buf.extend_from_slice(&[0x45, 0]);
+            buf.extend_from_slice(&total_len.to_be_bytes());
+            // identification, flags (don't fragment), fragment offset
+            buf.extend_from_slice(&[0, 0, 0x40, 0]);
+            // ttl, protocol
+            buf.extend_from_slice(&[64, proto]);
+            // header checksum
+            buf.extend_from_slice(&[0; 2]);
+            buf.extend_from_slice(&src.octets());
+            buf.extend_from_slice(&dst.octets());
+
+            let checksum = !fold(sum(&buf));
+            buf[10..12].copy_from_slice(&checksum.to_be_bytes());
+        }
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            // version 6, traffic class, flow label
+            buf.extend_from_slice(&[0x60, 0, 0, 0]);
+            buf.extend_from_slice(&len16(payload_len).to_be_bytes());
+            // next header, hop limit
+            buf.extend_from_slice(&[proto, 64]);
+            buf.extend_from_slice(&src.octets());
+            buf.extend_from_slice(&dst.octets());
+        }
+        _ => unreachable!("ip version mismatch: {src} -> {dst}"),
+    }
+
+    buf
+}
+
+/// Fill in the TCP/UDP checksum, which covers a pseudo header of the IP
+/// addresses, protocol and length.
+fn checksum_l4(src: IpAddr, dst: IpAddr, proto: u8, l4: &mut [u8]) {
+    let mut pseudo = Vec::with_capacity(40);
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            pseudo.extend_from_slice(&src.octets());
+            pseudo.extend_from_slice(&dst.octets());
+        }
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            pseudo.extend_from_slice(&src.octets());
+            pseudo.extend_from_slice(&dst.octets());
+        }
+        _ => unreachable!("ip version mismatch: {src} -> {dst}"),
+    }
+    pseudo.extend_from_slice(&[0, proto]);
+    pseudo.extend_from_slice(&len16(l4.len()).to_be_bytes());
+
+    let offset = if proto == PROTO_TCP { 16 } else { 6 };
+
+    let mut checksum = !fold(sum(&pseudo) + sum(l4));
+    if proto == PROTO_UDP && checksum == 0 {
+        // Zero means "no checksum" for UDP
+        checksum = 0xffff;
+    }
+
+    l4[offset..offset + 2].copy_from_slice(&checksum.to_be_bytes());
+}
+
+fn sum(bytes: &[u8]) -> u64 {
+    bytes
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u64)
+        .sum()
+}
+
+fn fold(mut sum: u64) -> u16 {
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+#[cfg(test)]
+mod test {
+    use super::{checksum_l4, fold, ip, sum, udp, Pcap, PROTO_UDP, SNAPLEN};
+    use crate::envelope::{Datagram, Protocol, Segment};
+    use bytes::Bytes;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    #[test]
+    fn truncated_packet_headers_describe_full_packet() -> std::io::Result<()> {
+        let name = format!("turmoil-pcap-truncated-{}.pcap", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let src: SocketAddr = "[fe80::1]:1234".parse().unwrap();
+        let dst: SocketAddr = "[fe80::2]:1234".parse().unwrap();
+        let payload = Bytes::from(vec![7; 65500]);
+
+        let mut pcap = Pcap::create(&path, Duration::ZERO)?;
+        let datagram = Protocol::Udp(Datagram(payload.clone()));
+        pcap.deliver(Duration::ZERO, src, dst, &datagram)?;
+        drop(pcap);
+
+        let file = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+
+        let record = &file[24..];
+        let incl_len = u32::from_le_bytes(record[8..12].try_into().unwrap());
+        let orig_len = u32::from_le_bytes(record[12..16].try_into().unwrap());
+        assert_eq!(SNAPLEN, incl_len);
+        assert_eq!(40 + 8 + 65500, orig_len);
+
+        let packet = &record[16..];
+        assert_eq!(SNAPLEN as usize, packet.len());
+        assert_eq!(8 + 65500, u16::from_be_bytes([packet[4], packet[5]]));
+
+        let mut l4 = udp(src, dst, &payload);
+        checksum_l4(src.ip(), dst.ip(), PROTO_UDP, &mut l4);
+        assert_eq!(&l4[6..8], &packet[46..48]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn closed_streams_are_forgotten() -> std::io::Result<()> {
+        let name = format!("turmoil-pcap-closed-{}.pcap", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let src: SocketAddr = "192.168.0.1:1234".parse().unwrap();
+        let dst: SocketAddr = "192.168.0.2:1234".parse().unwrap();
+
+        let mut pcap = Pcap::create(&path, Duration::ZERO)?;
+        for segment in [
+            Segment::Data(1, Bytes::from_static(b"ping")),
+            Segment::Fin(2),
+        ] {
+            let segment = Protocol::Tcp(segment);
+            pcap.send(src, dst, &segment);
+            pcap.deliver(Duration::ZERO, src, dst, &segment)?;
+        }
+        assert!(pcap.flows.is_empty());
+
+        drop(pcap);
+        std::fs::remove_file(&path)
+    }
+
+    #[test]
+    fn ipv4_header_checksum() {
+        let header = ip(
+            "192.168.0.1".parse().unwrap(),
+            "192.168.0.2".parse().unwrap(),
+            17,
+            12,
+        );
+
+        // A valid header sums to 0xffff, including the checksum itself
+        assert_eq!(0xffff, fold(sum(&header)));
+    }
+}
@@ -1,12 +1,17 @@
+use std::future::poll_fn;
 use std::mem;
-use std::sync::Arc;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
 
 use super::Result;
+use futures::task::ArcWake;
 use futures::Future;
 use std::pin::Pin;
 use tokio::runtime::Runtime;
 use tokio::task::JoinHandle;
-use tokio::task::LocalSet;
+use tokio::task::{yield_now, LocalSet};
 use tokio::time::{sleep, Duration, Instant};
 
 // To support re-creation, we need to store a factory of the future that
@@ -48,14 +53,74 @@ pub(crate) struct Rt<'a> {
     /// Optional handle to a host's software. When software finishes, the handle is
     /// consumed to check for error, which is propagated up to fail the simulation.
     handle: Option<JoinHandle<Result>>,
+
+    /// Seed for the tokio runtime's internal random number generator, reused
+    /// when the runtime is recreated.
+    rng_seed: u64,
+
+    /// How far the runtime's clock is ahead of the simulation, after probing
+    /// for its next timer moved it past the simulation's next event. The
+    /// runtime is idle until the simulation catches up.
+    lead: Duration,
+
+    /// Whether the runtime runs on its next tick even if its lead covers it,
+    /// as its tasks were woken, e.g. by messages.
+    wake: bool,
+
+    /// Whether tasks were woken while the runtime was not running.
+    woken: Arc<Woken>,
+
+    /// How many ticks the runtime sat out.
+    #[cfg(test)]
+    pub(crate) skipped: usize,
+}
+
+/// Wraps the waker the [`LocalSet`] registers with, recording when tasks are
+/// woken from outside the runtime, e.g. by messages or other hosts' software.
+#[derive(Default)]
+struct Woken {
+    woken: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+
+    /// When the current probe started, if probing.
+    probing: Mutex<Option<Instant>>,
+}
+
+impl Woken {
+    fn wake_outer(&self) {
+        if let Some(waker) = self.waker.lock().unwrap().as_ref() {
+            waker.wake_by_ref();
+        }
+    }
+
+    // Timers fire as the runtime unparks, which wakes tasks spawned outside
+    // the `LocalSet` without going through it.
+    fn unparked(&self) {
+        if self
+            .probing
+            .lock()
+            .unwrap()
+            .is_some_and(|start| Instant::now() > start)
+        {
+            self.wake_outer();
+        }
+    }
+}
+
+impl ArcWake for Woken {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.woken.store(true, Ordering::SeqCst);
+        arc_self.wake_outer();
+    }
 }
 
 impl<'a> Rt<'a> {
-    pub(crate) fn client<F>(nodename: Arc<str>, client: F) -> Self
+    pub(crate) fn client<F>(nodename: Arc<str>, client: F, rng_seed: u64) -> Self
     where
         F: Future<Output = Result> + 'static,
     {
-        let (tokio, local) = init();
+        let woken = Arc::<Woken>::default();
+        let (tokio, local) = init(rng_seed, &woken);
 
         let handle = with(&tokio, &local, || tokio::task::spawn_local(client));
 
@@ -65,15 +130,22 @@ impl<'a> Rt<'a> {
             local,
             nodename,
             handle: Some(handle),
+            rng_seed,
+            lead: Duration::ZERO,
+            wake: false,
+            woken,
+            #[cfg(test)]
+            skipped: 0,
         }
     }
 
-    pub(crate) fn host<F, Fut>(nodename: Arc<str>, software: F) -> Self
+    pub(crate) fn host<F, Fut>(nodename: Arc<str>, software: F, rng_seed: u64) -> Self
     where
         F: Fn() -> Fut + 'a,
         Fut: Future<Output = Result> + 'static,
     {
-        let (tokio, local) = init();
+        let woken = Arc::<Woken>::default();
+        let (tokio, local) = init(rng_seed, &woken);
 
         let software: Software = Box::new(move || Box::pin(software()));
         let handle = with(&tokio, &local, || tokio::task::spawn_local(software()));
@@ -84,11 +156,18 @@ impl<'a> Rt<'a> {
             local,
             nodename,
             handle: Some(handle),
+            rng_seed,
+            lead: Duration::ZERO,
+            wake: false,
+            woken,
+            #[cfg(test)]
+            skipped: 0,
         }
     }
 
     pub(crate) fn no_software() -> Self {
-        let (tokio, local) = init();
+        let woken = Arc::<Woken>::default();
+        let (tokio, local) = init(0, &woken);
 
         Self {
             kind: Kind::NoSoftware,
@@ -96,6 +175,12 @@ impl<'a> Rt<'a> {
             local,
             nodename: String::new().into(),
             handle: None,
+            rng_seed: 0,
+            lead: Duration::ZERO,
+            wake: false,
+            woken,
+            #[cfg(test)]
+            skipped: 0,
         }
     }
 
@@ -111,9 +196,10 @@ impl<'a> Rt<'a> {
         self.handle.is_some()
     }
 
+    /// The runtime's clock, less any lead it has over the simulation.
     pub(crate) fn now(&self) -> Instant {
         let _guard = self.tokio.enter();
-        Instant::now()
+        Instant::now() - self.lead
     }
 
     // This method is called by [`Sim::run`], which iterates through all the
@@ -138,14 +224,35 @@ impl<'a> Rt<'a> {
     // Returns whether the software has finished successfully or the error
     // that caused failure. Subsequent calls do not return the error as it is
     // expected to fail the simulation.
+    //
+    // A zero `duration` (e.g. for a host with a slow clock) still gives tasks
+    // a chance to run, without moving time forward. Any lead the runtime has
+    // over the simulation is used up before its clock moves again, and the
+    // runtime sits out ticks that its lead covers, as nothing is due to run.
+    // A runtime woken while ahead runs its tasks without its clock moving.
     pub(crate) fn tick(&mut self, duration: Duration) -> Result<bool> {
-        self.tokio.block_on(async {
-            self.local
-                .run_until(async {
+        let wake = mem::take(&mut self.wake);
+
+        if !self.lead.is_zero() && self.lead >= duration && !wake {
+            self.lead -= duration;
+
+            #[cfg(test)]
+            {
+                self.skipped += 1;
+            }
+        } else {
+            let lead = self.lead;
+            self.lead = lead.saturating_sub(duration);
+            let duration = duration.saturating_sub(lead);
+
+            self.run_until(async {
+                if duration.is_zero() {
+                    yield_now().await;
+                } else {
                     sleep(duration).await;
-                })
-                .await
-        });
+                }
+            });
+        }
 
         // pull for software completion
         match &self.handle {
@@ -166,6 +273,91 @@ impl<'a> Rt<'a> {
         }
     }
 
+    /// Run tasks that are ready, then jump the runtime's clock forward to the
+    /// next timer that wakes a task, but no further than `max` ahead of the
+    /// simulation. The woken task runs the next time the runtime ticks.
+    ///
+    /// Returns how far ahead of the simulation the clock is, which is zero if
+    /// tasks are still ready to run. A runtime that is already ahead is left
+    /// alone, as running its tasks now would fire its next timer early.
+    pub(crate) fn probe(&mut self, max: Duration) -> Duration {
+        if self.lead.is_zero() {
+            self.wake = false;
+            self.lead = self.advance_until_woken(max);
+        }
+
+        self.lead.min(max)
+    }
+
+    /// Run the runtime's tasks on its next tick, e.g. once messages are
+    /// delivered to the host. A clock that is ahead can't move back, so it
+    /// stays ahead until the simulation catches up.
+    pub(crate) fn wake(&mut self) {
+        self.wake = true;
+    }
+
+    /// Whether the runtime's clock is ahead of the simulation, with tasks
+    /// woken by its next timer waiting for the simulation to catch up.
+    pub(crate) fn is_ahead(&self) -> bool {
+        !self.lead.is_zero()
+    }
+
+    /// Whether tasks were woken since the runtime last ran, e.g. by another
+    /// host's software.
+    pub(crate) fn is_woken(&self) -> bool {
+        self.woken.woken.load(Ordering::SeqCst)
+    }
+
+    // The paused clock auto-advances to the next timer once the runtime is
+    // idle. Firing a timer wakes the `LocalSet`, which polls the future below
+    // again before running the woken task.
+    fn advance_until_woken(&mut self, max: Duration) -> Duration {
+        let _guard = self.tokio.enter();
+        let start = Instant::now();
+        let mut sleep = pin!(sleep(max));
+        let mut polled = false;
+
+        *self.woken.probing.lock().unwrap() = Some(start);
+        self.run_until(poll_fn(|cx| {
+            if sleep.as_mut().poll(cx).is_ready() || mem::replace(&mut polled, true) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }));
+        *self.woken.probing.lock().unwrap() = None;
+
+        Instant::now() - start
+    }
+
+    /// Drive the [`LocalSet`] until `future` completes. Tasks woken while
+    /// doing so are handled here, or by the next tick.
+    fn run_until<F: Future>(&self, future: F) -> F::Output {
+        let woken = self.woken.clone();
+        let waker = futures::task::waker(woken.clone());
+        let mut future = pin!(self.local.run_until(future));
+
+        let output = self.tokio.block_on(poll_fn(|cx| {
+            *woken.waker.lock().unwrap() = Some(cx.waker().clone());
+            future.as_mut().poll(&mut Context::from_waker(&waker))
+        }));
+
+        woken.woken.store(false, Ordering::SeqCst);
+        output
+    }
+
+    /// Jump the runtime's clock forward by `duration`, without running any
+    /// tasks. Timers that elapse fire the next time the runtime ticks. Any
+    /// lead the runtime has over the simulation is used up first.
+    pub(crate) fn advance(&mut self, duration: Duration) {
+        if self.lead >= duration {
+            self.lead -= duration;
+        } else {
+            let duration = duration - mem::take(&mut self.lead);
+            self.tokio.block_on(tokio::time::advance(duration));
+        }
+    }
+
     pub(crate) fn crash(&mut self) {
         if !self.is_host() {
             panic!("can only crash host's software");
@@ -200,20 +392,40 @@ impl<'a> Rt<'a> {
     ///
     /// Both the [`Runtime`] and [`LocalSet`] are replaced with new instances.
     fn cancel_tasks(&mut self) {
-        let (tokio, local) = init();
+        self.woken = Default::default();
+        let (tokio, local) = init(self.rng_seed, &self.woken);
 
         _ = mem::replace(&mut self.tokio, tokio);
         drop(mem::replace(&mut self.local, local));
+        self.lead = Duration::ZERO;
+        self.wake = false;
     }
 }
 
-fn init() -> (Runtime, LocalSet) {
+// The `rng_seed` makes tokio's internal randomness, such as `select!` branch
+// ordering, deterministic. This is only configurable with `tokio_unstable`.
+//
+// While probing, `woken` polls the runtime's top level future as soon as the
+// paused clock moves, before any tasks run, which lets [`Rt::probe`] stop at
+// the first timer, including for tasks spawned outside of the `LocalSet`.
+fn init(rng_seed: u64, woken: &Arc<Woken>) -> (Runtime, LocalSet) {
     let mut builder = tokio::runtime::Builder::new_current_thread();
 
     #[cfg(tokio_unstable)]
-    builder.unhandled_panic(tokio::runtime::UnhandledPanic::ShutdownRuntime);
-
-    let tokio = builder.enable_time().start_paused(true).build().unwrap();
+    builder
+        .unhandled_panic(tokio::runtime::UnhandledPanic::ShutdownRuntime)
+        .rng_seed(tokio::runtime::RngSeed::from_bytes(&rng_seed.to_le_bytes()));
+
+    #[cfg(not(tokio_unstable))]
+    let _ = rng_seed;
+
+    let woken = woken.clone();
+    let tokio = builder
+        .enable_time()
+        .start_paused(true)
+        .on_thread_unpark(move || woken.unparked())
+        .build()
+        .unwrap();
 
     tokio.block_on(async {
         // Sleep to "round" `Instant::now()` to the closest `ms`
@@ -0,0 +1,181 @@
+use crate::config;
+use crate::ToIpAddrs;
+
+use std::cell::RefCell;
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// A timeline of faults, applied automatically as the simulation steps.
+///
+/// Each fault is applied on the first [`Sim::step`] at or after its scheduled
+/// simulated time. Faults scheduled for the same time are applied in the order
+/// they were added.
+///
+/// ```
+/// use std::time::Duration;
+/// use turmoil::FaultSchedule;
+///
+/// let mut sim = turmoil::Builder::new().build();
+///
+/// let mut schedule = FaultSchedule::new();
+/// schedule
+///     .partition(Duration::from_secs(2), "a", "b")
+///     .repair(Duration::from_secs(3), "a", "b")
+///     .link_latency(
+///         Duration::from_secs(3)..Duration::from_secs(4),
+///         "a",
+///         "c",
+///         Duration::from_millis(500),
+///     )
+///     .bounce(Duration::from_secs(5), "c");
+///
+/// sim.schedule_faults(schedule);
+/// ```
+///
+/// [`Sim::step`]: crate::Sim::step
+#[derive(Default)]
+pub struct FaultSchedule {
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    at: Duration,
+    fault: Fault,
+}
+
+pub(crate) type Hosts = Rc<dyn ToIpAddrs>;
+
+/// Link latency overrides, per direction, saved when a latency window opens and
+/// restored when it closes.
+pub(crate) type SavedLatency = Rc<RefCell<Vec<(IpAddr, IpAddr, Option<config::Latency>)>>>;
+
+pub(crate) enum Fault {
+    Partition(Hosts, Hosts),
+    Repair(Hosts, Hosts),
+    Hold(Hosts, Hosts),
+    Release(Hosts, Hosts),
+    Crash(Hosts),
+    Bounce(Hosts),
+    Latency(Hosts, Hosts, Duration, SavedLatency),
+    RestoreLatency(SavedLatency),
+}
+
+impl FaultSchedule {
+    /// Create an empty schedule. Add faults with the methods below, then
+    /// pass it to [`Sim::schedule_faults`].
+    ///
+    /// [`Sim::schedule_faults`]: crate::Sim::schedule_faults
+    pub fn new() -> FaultSchedule {
+        FaultSchedule::default()
+    }
+
+    /// Partition two hosts, or sets of hosts, at `at`.
+    pub fn partition(
+        &mut self,
+        at: Duration,
+        a: impl ToIpAddrs + 'static,
+        b: impl ToIpAddrs + 'static,
+    ) -> &mut Self {
+        self.push(at, Fault::Partition(Rc::new(a), Rc::new(b)))
+    }
+
+    /// Repair the connection between two hosts, or sets of hosts, at `at`.
+    pub fn repair(
+        &mut self,
+        at: Duration,
+        a: impl ToIpAddrs + 'static,
+        b: impl ToIpAddrs + 'static,
+    ) -> &mut Self {
+        self.push(at, Fault::Repair(Rc::new(a), Rc::new(b)))
+    }
+
+    /// Hold messages between two hosts, or sets of hosts, at `at`.
+    pub fn hold(
+        &mut self,
+        at: Duration,
+        a: impl ToIpAddrs + 'static,
+        b: impl ToIpAddrs + 'static,
+    ) -> &mut Self {
+        self.push(at, Fault::Hold(Rc::new(a), Rc::new(b)))
+    }
+
+    /// Release held messages between two hosts, or sets of hosts, at `at`.
+    pub fn release(
+        &mut self,
+        at: Duration,
+        a: impl ToIpAddrs + 'static,
+        b: impl ToIpAddrs + 'static,
+    ) -> &mut Self {
+        self.push(at, Fault::Release(Rc::new(a), Rc::new(b)))
+    }
+
+    /// Crash the resolved hosts at `at`. The simulation fails if any of them
+    /// is a client, as only software added with [`Sim::host`] can be
+    /// crashed.
+    ///
+    /// [`Sim::host`]: crate::Sim::host
+    pub fn crash(&mut self, at: Duration, addrs: impl ToIpAddrs + 'static) -> &mut Self {
+        self.push(at, Fault::Crash(Rc::new(addrs)))
+    }
+
+    /// Bounce the resolved hosts at `at`. The simulation fails if any of them
+    /// is a client, as only software added with [`Sim::host`] can be
+    /// restarted.
+    ///
+    /// [`Sim::host`]: crate::Sim::host
+    pub fn bounce(&mut self, at: Duration, addrs: impl ToIpAddrs + 'static) -> &mut Self {
+        self.push(at, Fault::Bounce(Rc::new(addrs)))
+    }
+
+    /// Set the message latency between two hosts, or sets of hosts, for the
+    /// duration of `window`. Once the window closes, the links' previous
+    /// latency configuration is restored. Panics if `window` ends before it
+    /// starts.
+    pub fn link_latency(
+        &mut self,
+        window: std::ops::Range<Duration>,
+        a: impl ToIpAddrs + 'static,
+        b: impl ToIpAddrs + 'static,
+        latency: Duration,
+    ) -> &mut Self {
+        assert!(
+            window.start <= window.end,
+            "latency window must not end before it starts: {window:?}"
+        );
+
+        let saved = SavedLatency::default();
+
+        self.push(
+            window.start,
+            Fault::Latency(Rc::new(a), Rc::new(b), latency, saved.clone()),
+        );
+        self.push(window.end, Fault::RestoreLatency(saved))
+    }
+
+    /// Move all faults from `other` into this schedule.
+    pub(crate) fn merge(&mut self, other: FaultSchedule) {
+        for entry in other.entries {
+            self.push(entry.at, entry.fault);
+        }
+    }
+
+    /// When the next fault is due.
+    pub(crate) fn next_at(&self) -> Option<Duration> {
+        self.entries.first().map(|e| e.at)
+    }
+
+    /// Remove and return the faults that are due at `now`, in order.
+    pub(crate) fn due(&mut self, now: Duration) -> Vec<Fault> {
+        let n = self.entries.partition_point(|e| e.at <= now);
+        self.entries.drain(..n).map(|e| e.fault).collect()
+    }
+
+    // Keeps entries ordered by time, preserving insertion order for faults
+    // scheduled at the same time.
+    fn push(&mut self, at: Duration, fault: Fault) -> &mut Self {
+        let i = self.entries.partition_point(|e| e.at <= at);
+        self.entries.insert(i, Entry { at, fault });
+        self
+    }
+}
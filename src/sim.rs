@@ -1,8 +1,19 @@
-use crate::{for_pairs, Config, LinksIter, Result, Rt, ToIpAddr, ToIpAddrs, World, TRACING_TARGET};
+use crate::error::SeedError;
+use crate::nemesis;
+use crate::schedule::Fault;
+use crate::trace::{Event, Trace};
+use crate::{
+    for_pairs, BurstLoss, ClockSkew, Config, Corruption, Envelope, FaultSchedule,
+    LatencyDistribution, LinksIter, Result, Rt, Stats, ToIpAddr, ToIpAddrs, Verdict, World,
+    TRACING_TARGET,
+};
 
 use indexmap::IndexMap;
+use rand::seq::SliceRandom;
+use rand::RngCore;
 use std::cell::RefCell;
 use std::future::Future;
+use std::mem;
 use std::net::IpAddr;
 use std::ops::DerefMut;
 use std::sync::Arc;
@@ -29,24 +40,49 @@ pub struct Sim<'a> {
 
     /// Simulation elapsed time
     elapsed: Duration,
+
+    /// Seed used for the random number generator, if known.
+    seed: Option<u64>,
+
+    /// Faults to apply as the simulation steps.
+    faults: FaultSchedule,
+
+    /// Injects faults at random.
+    nemesis: nemesis::Runner,
 }
 
 impl<'a> Sim<'a> {
-    pub(crate) fn new(config: Config, world: World) -> Self {
+    pub(crate) fn new(config: Config, world: World, seed: Option<u64>) -> Self {
         let since_epoch = config
             .epoch
             .duration_since(UNIX_EPOCH)
             .expect("now must be >= UNIX_EPOCH");
 
+        let nemesis = nemesis::Runner::new(config.nemesis.clone());
+
         Self {
             config,
             world: RefCell::new(world),
             rts: IndexMap::new(),
             since_epoch,
             elapsed: Duration::ZERO,
+            seed,
+            faults: FaultSchedule::new(),
+            nemesis,
         }
     }
 
+    /// The seed used for the random number generator.
+    ///
+    /// Set the `TURMOIL_SEED` environment variable (or use [`Builder::seed`])
+    /// to replay a simulation. Returns `None` if the simulation was built with
+    /// a custom rng.
+    ///
+    /// [`Builder::seed`]: crate::Builder::seed
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
     /// How much logical time has elapsed since the simulation started.
     pub fn elapsed(&self) -> Duration {
         self.elapsed
@@ -54,9 +90,12 @@ impl<'a> Sim<'a> {
 
     /// The logical duration from [`UNIX_EPOCH`] until now.
     ///
-    /// On creation the simulation picks a `SystemTime` and calculates the
-    /// duration since the epoch. Each `run()` invocation moves logical time
+    /// On creation the simulation calculates the duration since the configured
+    /// epoch (see [`Builder::epoch`]), which defaults to [`UNIX_EPOCH`] to keep
+    /// simulations deterministic. Each `run()` invocation moves logical time
     /// forward the configured tick duration.
+    ///
+    /// [`Builder::epoch`]: crate::Builder::epoch
     pub fn since_epoch(&self) -> Duration {
         self.since_epoch + self.elapsed
     }
@@ -76,14 +115,17 @@ impl<'a> Sim<'a> {
             .unwrap_or_else(|| addr.to_string())
             .into();
 
-        {
+        let since_epoch = self.since_epoch();
+        let rng_seed = {
             let world = RefCell::get_mut(&mut self.world);
 
             // Register host state with the world
-            world.register(addr, &nodename, &self.config);
-        }
+            world.register(addr, &nodename, &self.config, since_epoch);
 
-        let rt = World::enter(&self.world, || Rt::client(nodename, client));
+            world.rng.next_u64()
+        };
+
+        let rt = World::enter(&self.world, || Rt::client(nodename, client, rng_seed));
 
         self.rts.insert(addr, rt);
     }
@@ -109,14 +151,17 @@ impl<'a> Sim<'a> {
             .unwrap_or_else(|| addr.to_string())
             .into();
 
-        {
+        let since_epoch = self.since_epoch();
+        let rng_seed = {
             let world = RefCell::get_mut(&mut self.world);
 
             // Register host state with the world
-            world.register(addr, &nodename, &self.config);
-        }
+            world.register(addr, &nodename, &self.config, since_epoch);
 
-        let rt = World::enter(&self.world, || Rt::host(nodename, host));
+            world.rng.next_u64()
+        };
+
+        let rt = World::enter(&self.world, || Rt::host(nodename, host, rng_seed));
 
         self.rts.insert(addr, rt);
     }
@@ -128,6 +173,8 @@ impl<'a> Sim<'a> {
         self.run_with_hosts(addrs, |addr, rt| {
             rt.crash();
 
+            World::current(|world| world.trace.record(|| Event::Crash(addr)));
+
             tracing::trace!(target: TRACING_TARGET, addr = ?addr, "Crash");
         });
     }
@@ -137,10 +184,73 @@ impl<'a> Sim<'a> {
         self.run_with_hosts(addrs, |addr, rt| {
             rt.bounce();
 
+            World::current(|world| world.trace.record(|| Event::Bounce(addr)));
+
             tracing::trace!(target: TRACING_TARGET, addr = ?addr, "Bounce");
         });
     }
 
+    /// Skew the clocks of the resolved hosts relative to simulated time.
+    ///
+    /// The skew applies to the hosts' wall clock (see [`crate::now`]). The
+    /// monotonic clock is also affected: moving a clock forward jumps the host's
+    /// [`tokio::time::Instant`] ahead (firing any elapsed timers), while moving
+    /// it backwards has no effect on `Instant`.
+    pub fn set_clock_skew(&mut self, addrs: impl ToIpAddrs, skew: ClockSkew) {
+        self.run_with_hosts(addrs, |addr, rt| {
+            let jump = World::current(|world| world.current_host_mut().clock.set_skew(skew));
+
+            if let Some(jump) = jump {
+                rt.advance(jump);
+            }
+
+            tracing::trace!(target: TRACING_TARGET, ?addr, ?skew, "Skew");
+        });
+    }
+
+    /// Step the wall clock (see [`crate::now`]) of the resolved hosts ahead or
+    /// behind, as an NTP step would.
+    ///
+    /// Unlike [`Sim::set_clock_skew`], this does not affect the hosts'
+    /// [`tokio::time::Instant`].
+    pub fn step_wall_clock(&self, addrs: impl ToIpAddrs, step: ClockSkew) {
+        let mut world = self.world.borrow_mut();
+
+        for addr in world.lookup_many(addrs) {
+            world
+                .hosts
+                .get_mut(&addr)
+                .expect("missing host")
+                .clock
+                .step(step);
+        }
+    }
+
+    /// Set the clock drift of the resolved hosts in parts per million.
+    ///
+    /// A positive `ppm` makes the host's clock run fast, so its timers fire
+    /// early relative to simulated time. A negative `ppm` makes it run slow.
+    ///
+    /// Panics if `ppm` is not finite, or is -1,000,000 or less, which would stop
+    /// the clock or run it backwards.
+    pub fn set_clock_drift(&mut self, addrs: impl ToIpAddrs, ppm: f64) {
+        assert!(
+            ppm.is_finite() && ppm > -1_000_000.0,
+            "invalid clock drift: {ppm} ppm"
+        );
+
+        let mut world = self.world.borrow_mut();
+
+        for addr in world.lookup_many(addrs) {
+            world
+                .hosts
+                .get_mut(&addr)
+                .expect("missing host")
+                .clock
+                .set_drift(ppm);
+        }
+    }
+
     /// Run `f` with the resolved hosts at `addrs` set on the world.
     fn run_with_hosts(&mut self, addrs: impl ToIpAddrs, mut f: impl FnMut(IpAddr, &mut Rt)) {
         let hosts = self.world.borrow_mut().lookup_many(addrs);
@@ -206,6 +316,35 @@ impl<'a> Sim<'a> {
         world.partition_many(a, b);
     }
 
+    /// Partition messages sent from one host, or set of hosts, to another.
+    /// Messages sent in the other direction are unaffected.
+    pub fn partition_oneway(&self, from: impl ToIpAddrs, to: impl ToIpAddrs) {
+        let mut world = self.world.borrow_mut();
+        world.partition_oneway_many(from, to);
+    }
+
+    /// Repair messages sent from one host, or set of hosts, to another. The
+    /// opposite of [`Sim::partition_oneway`].
+    pub fn repair_oneway(&self, from: impl ToIpAddrs, to: impl ToIpAddrs) {
+        let mut world = self.world.borrow_mut();
+        world.repair_oneway_many(from, to);
+    }
+
+    /// Hold messages sent from one host, or set of hosts, to another until
+    /// [`Sim::release_oneway`] is called. Messages sent in the other direction
+    /// are unaffected.
+    pub fn hold_oneway(&self, from: impl ToIpAddrs, to: impl ToIpAddrs) {
+        let mut world = self.world.borrow_mut();
+        world.hold_oneway_many(from, to);
+    }
+
+    /// The opposite of [`Sim::hold_oneway`]. Held messages sent from one host,
+    /// or set of hosts, to another are immediately delivered.
+    pub fn release_oneway(&self, from: impl ToIpAddrs, to: impl ToIpAddrs) {
+        let mut world = self.world.borrow_mut();
+        world.release_oneway_many(from, to);
+    }
+
     /// Resolve host names for an [`IpAddr`] pair.
     ///
     /// Useful when interacting with network [links](#method.links).
@@ -249,7 +388,28 @@ impl<'a> Sim<'a> {
         let b = world.lookup_many(b);
 
         for_pairs(&a, &b, |a, b| {
-            world.topology.set_link_message_latency(a, b, value);
+            for (from, to) in [(a, b), (b, a)] {
+                world.trace.record(|| Event::LinkLatency(from, to, value));
+                world.topology.set_link_message_latency(from, to, value);
+            }
+        });
+    }
+
+    /// Set the message latency for messages sent from `from` to `to`, leaving
+    /// the other direction unchanged.
+    pub fn set_link_latency_oneway(
+        &self,
+        from: impl ToIpAddrs,
+        to: impl ToIpAddrs,
+        value: Duration,
+    ) {
+        let mut world = self.world.borrow_mut();
+        let from = world.lookup_many(from);
+        let to = world.lookup_many(to);
+
+        for_pairs(&from, &to, |from, to| {
+            world.trace.record(|| Event::LinkLatency(from, to, value));
+            world.topology.set_link_message_latency(from, to, value);
         });
     }
 
@@ -266,6 +426,24 @@ impl<'a> Sim<'a> {
 
         for_pairs(&a, &b, |a, b| {
             world.topology.set_link_max_message_latency(a, b, value);
+            world.topology.set_link_max_message_latency(b, a, value);
+        });
+    }
+
+    /// Set the max message latency for messages sent from `from` to `to`,
+    /// leaving the other direction unchanged.
+    pub fn set_link_max_message_latency_oneway(
+        &self,
+        from: impl ToIpAddrs,
+        to: impl ToIpAddrs,
+        value: Duration,
+    ) {
+        let mut world = self.world.borrow_mut();
+        let from = world.lookup_many(from);
+        let to = world.lookup_many(to);
+
+        for_pairs(&from, &to, |from, to| {
+            world.topology.set_link_max_message_latency(from, to, value);
         });
     }
 
@@ -280,6 +458,42 @@ impl<'a> Sim<'a> {
             .set_message_latency_curve(value);
     }
 
+    /// Set the message latency distribution for all links.
+    ///
+    /// Panics if the distribution's parameters are invalid (see
+    /// [`Builder::latency_distribution`](crate::Builder::latency_distribution)).
+    pub fn set_latency_distribution(&self, value: LatencyDistribution) {
+        self.world
+            .borrow_mut()
+            .topology
+            .set_latency_distribution(value);
+    }
+
+    /// Set the message latency distribution for any links matching `a` and
+    /// `b`.
+    ///
+    /// Panics if the distribution's parameters are invalid (see
+    /// [`Builder::latency_distribution`](crate::Builder::latency_distribution)).
+    pub fn set_link_latency_distribution(
+        &self,
+        a: impl ToIpAddrs,
+        b: impl ToIpAddrs,
+        value: LatencyDistribution,
+    ) {
+        let mut world = self.world.borrow_mut();
+        let a = world.lookup_many(a);
+        let b = world.lookup_many(b);
+
+        for_pairs(&a, &b, |a, b| {
+            world
+                .topology
+                .set_link_latency_distribution(a, b, value.clone());
+            world
+                .topology
+                .set_link_latency_distribution(b, a, value.clone());
+        });
+    }
+
     pub fn set_fail_rate(&mut self, value: f64) {
         self.world.borrow_mut().topology.set_fail_rate(value);
     }
@@ -291,14 +505,365 @@ impl<'a> Sim<'a> {
 
         for_pairs(&a, &b, |a, b| {
             world.topology.set_link_fail_rate(a, b, value);
+            world.topology.set_link_fail_rate(b, a, value);
         });
     }
 
+    /// Set the fail rate for messages sent from `from` to `to`, leaving the
+    /// other direction unchanged.
+    pub fn set_link_fail_rate_oneway(
+        &mut self,
+        from: impl ToIpAddrs,
+        to: impl ToIpAddrs,
+        value: f64,
+    ) {
+        let mut world = self.world.borrow_mut();
+        let from = world.lookup_many(from);
+        let to = world.lookup_many(to);
+
+        for_pairs(&from, &to, |from, to| {
+            world.topology.set_link_fail_rate(from, to, value);
+        });
+    }
+
+    /// Set the probability of individual messages sent between any hosts
+    /// matching `a` and `b` being lost. See [`Builder::loss_rate`].
+    ///
+    /// [`Builder::loss_rate`]: crate::Builder::loss_rate
+    pub fn set_link_loss_rate(&mut self, a: impl ToIpAddrs, b: impl ToIpAddrs, value: f64) {
+        let mut world = self.world.borrow_mut();
+        let a = world.lookup_many(a);
+        let b = world.lookup_many(b);
+
+        for_pairs(&a, &b, |a, b| {
+            world.topology.set_link_loss_rate(a, b, value);
+            world.topology.set_link_loss_rate(b, a, value);
+        });
+    }
+
+    /// Set the bursty loss model for any links matching `a` and `b`. `None`
+    /// disables burst loss.
+    pub fn set_link_burst_loss(
+        &mut self,
+        a: impl ToIpAddrs,
+        b: impl ToIpAddrs,
+        value: Option<BurstLoss>,
+    ) {
+        let mut world = self.world.borrow_mut();
+        let a = world.lookup_many(a);
+        let b = world.lookup_many(b);
+
+        for_pairs(&a, &b, |a, b| {
+            world.topology.set_link_burst_loss(a, b, value.clone());
+            world.topology.set_link_burst_loss(b, a, value.clone());
+        });
+    }
+
+    /// Corrupt message payloads sent between any hosts matching `a` and `b`.
+    /// See [`Builder::corruption`].
+    ///
+    /// [`Builder::corruption`]: crate::Builder::corruption
+    pub fn set_link_corruption(&mut self, a: impl ToIpAddrs, b: impl ToIpAddrs, value: Corruption) {
+        let mut world = self.world.borrow_mut();
+        let a = world.lookup_many(a);
+        let b = world.lookup_many(b);
+
+        for_pairs(&a, &b, |a, b| {
+            world.topology.set_link_corruption(a, b, value.clone());
+            world.topology.set_link_corruption(b, a, value.clone());
+        });
+    }
+
+    /// Limit how many payload bytes per second the links between any hosts
+    /// matching `a` and `b` carry in each direction. `None` removes the limit.
+    /// See [`Builder::bandwidth`].
+    ///
+    /// [`Builder::bandwidth`]: crate::Builder::bandwidth
+    pub fn set_link_bandwidth(
+        &mut self,
+        a: impl ToIpAddrs,
+        b: impl ToIpAddrs,
+        bytes_per_sec: Option<u64>,
+    ) {
+        let mut world = self.world.borrow_mut();
+        let a = world.lookup_many(a);
+        let b = world.lookup_many(b);
+
+        for_pairs(&a, &b, |a, b| {
+            world.topology.set_link_bandwidth(a, b, bytes_per_sec);
+            world.topology.set_link_bandwidth(b, a, bytes_per_sec);
+        });
+    }
+
+    /// Set the probability of UDP datagrams sent between any hosts matching
+    /// `a` and `b` being delivered twice.
+    pub fn set_link_duplicate_rate(&mut self, a: impl ToIpAddrs, b: impl ToIpAddrs, value: f64) {
+        let mut world = self.world.borrow_mut();
+        let a = world.lookup_many(a);
+        let b = world.lookup_many(b);
+
+        for_pairs(&a, &b, |a, b| {
+            world.topology.set_link_duplicate_rate(a, b, value);
+            world.topology.set_link_duplicate_rate(b, a, value);
+        });
+    }
+
+    /// Set the probability of UDP datagrams sent between any hosts matching
+    /// `a` and `b` being reordered. See [`Builder::reorder_rate`].
+    ///
+    /// [`Builder::reorder_rate`]: crate::Builder::reorder_rate
+    pub fn set_link_reorder_rate(&mut self, a: impl ToIpAddrs, b: impl ToIpAddrs, value: f64) {
+        let mut world = self.world.borrow_mut();
+        let a = world.lookup_many(a);
+        let b = world.lookup_many(b);
+
+        for_pairs(&a, &b, |a, b| {
+            world.topology.set_link_reorder_rate(a, b, value);
+            world.topology.set_link_reorder_rate(b, a, value);
+        });
+    }
+
+    /// The events recorded so far, if enabled with [`Builder::record_trace`].
+    ///
+    /// [`Builder::record_trace`]: crate::Builder::record_trace
+    pub fn trace(&self) -> Trace {
+        self.world.borrow().trace.trace().clone()
+    }
+
+    /// Network counters accumulated so far, per link and per host.
+    pub fn stats(&self) -> Stats {
+        let world = self.world.borrow();
+
+        let mut stats = Stats::default();
+        world.topology.stats(&mut stats);
+        for (&addr, host) in &world.hosts {
+            stats.hosts.insert(addr, host.stats.clone());
+        }
+
+        stats
+    }
+
+    /// Schedule faults to be applied automatically as the simulation steps.
+    ///
+    /// Faults are merged with any previously scheduled faults. Times are
+    /// relative to the start of the simulation, so faults scheduled in the past
+    /// are applied on the next step.
+    pub fn schedule_faults(&mut self, schedule: FaultSchedule) {
+        self.faults.merge(schedule);
+    }
+
+    /// Apply scheduled faults that are due. Crashing or bouncing a client is
+    /// an error, as only hosts can be restarted.
+    fn apply_faults(&mut self) -> Result {
+        for fault in self.faults.due(self.elapsed) {
+            match fault {
+                Fault::Crash(addrs) => {
+                    let addrs = addrs.to_ip_addrs(&mut self.world.borrow_mut().dns);
+                    for addr in addrs {
+                        self.check_not_client(addr, "crash")?;
+                        self.crash(addr);
+                    }
+                }
+                Fault::Bounce(addrs) => {
+                    let addrs = addrs.to_ip_addrs(&mut self.world.borrow_mut().dns);
+                    for addr in addrs {
+                        self.check_not_client(addr, "bounce")?;
+                        self.bounce(addr);
+                    }
+                }
+                fault => self.world.borrow_mut().apply_fault(fault),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_not_client(&self, addr: IpAddr, fault: &str) -> Result {
+        match self.rts.get(&addr) {
+            Some(rt) if rt.is_client() => Err(format!(
+                "cannot {fault} client {}, only hosts can be crashed or bounced",
+                rt.nodename
+            ))?,
+            _ => Ok(()),
+        }
+    }
+
+    /// How far time may skip, once the network has ticked for this step,
+    /// before something outside of host runtimes is due: a message delivery,
+    /// TCP retransmission, scheduled fault or the end of the simulation. Time
+    /// also skips no further than any host's runtime may move ahead (see
+    /// `World::lookahead`), nor at all while a host is woken.
+    fn idle_bound(&self) -> Duration {
+        if self.nemesis.is_enabled() {
+            return Duration::ZERO;
+        }
+
+        let tick = self.config.tick;
+        let world = self.world.borrow();
+
+        // Events in host time are observed by the step that ticks up to them
+        let mut bound = self.config.duration.saturating_sub(self.elapsed + tick);
+        if let Some(at) = self.faults.next_at() {
+            bound = bound.min(at.saturating_sub(self.elapsed + tick));
+        }
+        for host in world.hosts.values() {
+            if host.woken {
+                return Duration::ZERO;
+            }
+            if let Some(timer) = host.next_timer() {
+                bound = bound.min(timer.saturating_sub(tick));
+            }
+        }
+
+        // The network is already a tick ahead of the hosts
+        if let Some(delivery) = world.topology.next_delivery() {
+            bound = bound.min(delivery);
+        }
+        for (&addr, _) in self.rts.iter().filter(|(_, rt)| rt.is_software_running()) {
+            bound = bound.min(world.lookahead(addr));
+        }
+
+        bound
+    }
+
+    /// The order hosts run in for a step, as indices into `rts`, shuffled if
+    /// [`Builder::random_host_order`] is set.
+    ///
+    /// [`Builder::random_host_order`]: crate::Builder::random_host_order
+    fn host_order(&self) -> Vec<usize> {
+        let mut order = (0..self.rts.len()).collect::<Vec<_>>();
+        if self.config.random_host_order {
+            order.shuffle(&mut self.world.borrow_mut().rng);
+        }
+
+        order
+    }
+
+    /// Move time forward to just before the next event, when nothing is due
+    /// sooner. The rest of the step runs the event as if every tick leading up
+    /// to it had been stepped.
+    fn skip_idle_time(&mut self, order: &[usize]) -> Result {
+        let tick = self.config.tick;
+
+        if self.idle_bound() < tick {
+            return Ok(());
+        }
+
+        // Tasks that are ready run now, as they would at the start of this
+        // tick, in case they send messages or arm timers. Runtimes that are
+        // ahead wait for the simulation to catch up.
+        let mut finished = false;
+        for &index in order {
+            let (&mut addr, rt) = self.rts.get_index_mut(index).expect("missing host");
+            if rt.is_software_running() && !rt.is_ahead() {
+                finished |= run_on(&self.world, addr, rt, |rt| rt.tick(Duration::ZERO))?;
+            }
+        }
+
+        // Software that completed is observed by this step, without skipping
+        let bound = self.idle_bound();
+        if finished || bound < tick {
+            return Ok(());
+        }
+
+        // Find the earliest timer across host runtimes, in each host's local
+        // time. A runtime's clock can't move back, so each host only moves as
+        // far ahead as nothing could wake it (see `World::lookahead`), and
+        // time skips no further than the host that moved least.
+        let mut skip = bound;
+        for &index in order {
+            let (&mut addr, rt) = self.rts.get_index_mut(index).expect("missing host");
+            if !rt.is_software_running() {
+                continue;
+            }
+
+            let max = self.world.borrow().hosts[&addr].clock.peek(skip);
+            let probed = if max.is_zero() {
+                Duration::ZERO
+            } else {
+                run_on(&self.world, addr, rt, |rt| rt.probe(max))
+            };
+
+            if probed < max {
+                let world = self.world.borrow();
+                skip = skip.min(world.hosts[&addr].clock.ticks_within(probed, tick));
+            }
+
+            if skip.is_zero() {
+                return Ok(());
+            }
+        }
+
+        // Whole ticks, so that events line up with the steps they would happen
+        // in when ticking
+        let skip = tick * (skip.as_nanos() / tick.as_nanos()) as u32;
+
+        self.elapsed += skip;
+
+        let mut world = self.world.borrow_mut();
+        world.trace.elapsed = self.elapsed;
+        world.topology.tick_by(skip);
+
+        for (addr, rt) in self.rts.iter_mut() {
+            let local = world
+                .hosts
+                .get_mut(addr)
+                .expect("missing host")
+                .clock
+                .tick(skip);
+
+            if rt.is_software_running() {
+                rt.advance(local);
+                world.tick(*addr, local);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Let the nemesis schedule random faults.
+    fn run_nemesis(&mut self) {
+        let hosts = self
+            .rts
+            .iter()
+            .filter(|(_, rt)| !rt.is_client())
+            .map(|(&addr, rt)| (addr, rt.is_software_running()))
+            .collect::<Vec<_>>();
+
+        let mut world = self.world.borrow_mut();
+        self.nemesis
+            .step(self.elapsed, &hosts, &mut world.rng, &mut self.faults);
+    }
+
     /// Access a [`LinksIter`] to introspect inflight messages between hosts.
+    /// Only links that have been used, e.g. to send a message, are included.
     pub fn links(&self, f: impl FnOnce(LinksIter)) {
-        let top = &mut self.world.borrow_mut().topology;
+        let world = &mut *self.world.borrow_mut();
 
-        f(top.iter_mut())
+        f(world.topology.iter_mut());
+        world.topology.remove_dropped(&mut world.trace);
+    }
+
+    /// Intercept every message sent onto the network. `filter` is invoked as
+    /// each message is sent and returns a [`Verdict`] deciding what happens to
+    /// it. Messages that are sent on are still subject to the link's faults.
+    ///
+    /// RSTs sent by the network in response to undeliverable TCP segments are
+    /// not filtered.
+    ///
+    /// The filter runs while the network is borrowed, so it must not call back
+    /// into the simulation, e.g. with [`lookup`](crate::lookup). Resolve
+    /// addresses ahead of time with [`Sim::lookup`].
+    pub fn set_network_filter(&mut self, filter: impl FnMut(&Envelope) -> Verdict + 'static) {
+        self.world
+            .borrow_mut()
+            .topology
+            .set_filter(Some(Box::new(filter)));
+    }
+
+    /// Remove the network filter, if one is set.
+    pub fn clear_network_filter(&mut self) {
+        self.world.borrow_mut().topology.set_filter(None);
     }
 
     /// Run the simulation to completion.
@@ -324,102 +889,573 @@ impl<'a> Sim<'a> {
     /// delivering them to their destination if appropriate.
     ///
     /// Returns whether or not all clients have completed.
+    ///
+    /// Errors include the seed (see [`Sim::seed`]) if it is known.
     pub fn step(&mut self) -> Result<bool> {
+        self.step_priv().map_err(|err| match self.seed {
+            Some(seed) => Box::new(SeedError::new(seed, err)),
+            None => err,
+        })
+    }
+
+    fn step_priv(&mut self) -> Result<bool> {
         let tick = self.config.tick;
 
-        let mut is_finished = true;
+        let mut is_finished = true;
+
+        let order = self.host_order();
+
+        self.world.borrow_mut().trace.elapsed = self.elapsed;
+
+        self.run_nemesis();
+        self.apply_faults()?;
+
+        // Tick the networking, processing messages. This is done before
+        // ticking any other runtime, as they might be waiting on network
+        // IO. (It also might be waiting on something else, such as time.)
+        self.world.borrow_mut().topology.tick_by(tick);
+
+        if self.config.event_driven {
+            self.skip_idle_time(&order)?;
+        }
+
+        // Host clocks keep moving while their software is not running.
+        for (addr, _) in self.rts.iter().filter(|(_, rt)| !rt.is_software_running()) {
+            let mut world = self.world.borrow_mut();
+            world
+                .hosts
+                .get_mut(addr)
+                .expect("missing host")
+                .clock
+                .tick(tick);
+        }
+
+        // Tick each host runtimes with running software. If the software
+        // completes, extract the result and return early if an error is
+        // encountered.
+        for index in order {
+            let (&mut addr, rt) = self.rts.get_index_mut(index).expect("missing host");
+            if !rt.is_software_running() {
+                continue;
+            }
+
+            let _span_guard = tracing::span!(Level::INFO, "node", name = &*rt.nodename).entered();
+
+            let local_tick = {
+                let mut world = self.world.borrow_mut();
+                // We need to move deliverable messages off the network and
+                // into the dst host. This requires two mutable borrows.
+                let World {
+                    rng,
+                    trace,
+                    topology,
+                    hosts,
+                    ..
+                } = world.deref_mut();
+                let delivered = topology.deliver_messages(
+                    rng,
+                    trace,
+                    hosts.get_mut(&addr).expect("missing host"),
+                );
+
+                // Messages and other hosts' wakeups are handled right away,
+                // even by a runtime that is ahead of the simulation
+                let host = hosts.get_mut(&addr).expect("missing host");
+                if delivered || mem::take(&mut host.woken) || rt.is_woken() {
+                    rt.wake();
+                }
+
+                trace.record(|| Event::Tick(addr));
+
+                // Set the current host (see method docs)
+                world.current = Some(addr);
+
+                world.current_host_mut().now(rt.now());
+
+                world.current_host_mut().clock.tick(tick)
+            };
+
+            // Move an idle runtime forward to its next timer, so that it sits
+            // out the steps until then. It goes no further than the host could
+            // be woken (see `World::lookahead`), or the end of the simulation.
+            if self.config.skip_idle_hosts {
+                let remaining = self.config.duration.saturating_sub(self.elapsed);
+                let max = self.world.borrow().lookahead(addr).min(remaining);
+
+                if !max.is_zero() {
+                    World::enter(&self.world, || rt.probe(max));
+                }
+            }
+
+            let is_software_finished = World::enter(&self.world, || rt.tick(local_tick))?;
+
+            if rt.is_client() {
+                is_finished = is_finished && is_software_finished;
+            }
+
+            // Unset the current host
+            let mut world = self.world.borrow_mut();
+            world.current = None;
+
+            world.tick(addr, local_tick);
+        }
+
+        self.elapsed += tick;
+
+        if self.elapsed > self.config.duration && !is_finished {
+            return Err(format!(
+                "Ran for {:?} without completing",
+                self.config.duration
+            ))?;
+        }
+
+        Ok(is_finished)
+    }
+}
+
+/// Run `f` on a host's runtime, with the host set as the current host.
+fn run_on<T>(world: &RefCell<World>, addr: IpAddr, rt: &mut Rt, f: impl FnOnce(&mut Rt) -> T) -> T {
+    {
+        let mut world = world.borrow_mut();
+        world.current = Some(addr);
+        world.current_host_mut().now(rt.now());
+    }
+
+    let res = World::enter(world, || f(rt));
+    world.borrow_mut().current = None;
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        rc::Rc,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use std::future;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        sync::Semaphore,
+        time::Instant,
+    };
+
+    use crate::{
+        elapsed, hold,
+        net::{TcpListener, TcpStream, UdpSocket},
+        trace::Event,
+        Builder, ClockSkew, FaultSchedule, LinkState, Nemesis, Result,
+    };
+
+    #[test]
+    fn client_error() {
+        let mut sim = Builder::new().build();
+
+        sim.client("doomed", async { Err("An Error")? });
+
+        assert!(sim.run().is_err());
+    }
+
+    #[test]
+    fn error_includes_seed() {
+        let mut sim = Builder::new().seed(42).build();
+
+        assert_eq!(Some(42), sim.seed());
+
+        sim.client("doomed", async { Err("An Error")? });
+
+        let err = sim.run().unwrap_err();
+        assert_eq!("An Error (replay with TURMOIL_SEED=42)", err.to_string());
+    }
+
+    #[test]
+    fn same_seed_same_events() -> Result {
+        Builder::new()
+            .fail_rate(0.2)
+            .repair_rate(0.5)
+            .check_determinism(|sim| {
+                sim.host("server", || async {
+                    let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+                    let mut buf = [0; 8];
+
+                    loop {
+                        let (n, origin) = sock.recv_from(&mut buf).await?;
+                        sock.send_to(&buf[..n], origin).await?;
+                    }
+                });
+
+                sim.client("client", async {
+                    let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+                    let mut buf = [0; 8];
+
+                    for i in 0..20u8 {
+                        sock.send_to(&[i], "server:1234").await?;
+                        let _ = tokio::time::timeout(
+                            Duration::from_millis(50),
+                            sock.recv_from(&mut buf),
+                        )
+                        .await;
+                    }
+
+                    Ok(())
+                });
+
+                sim.run()
+            })
+    }
+
+    #[test]
+    fn different_events_fail_determinism_check() {
+        let runs = Rc::new(AtomicU64::new(0));
+
+        let res = Builder::new().check_determinism(|sim| {
+            let sends = runs.fetch_add(1, Ordering::SeqCst) + 1;
+
+            sim.host("server", || async { future::pending().await });
+
+            sim.client("client", async move {
+                let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+
+                for _ in 0..sends {
+                    sock.send_to(&[0], "server:1234").await?;
+                }
+
+                Ok(())
+            });
+
+            sim.run()
+        });
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn record_trace() -> Result {
+        let mut sim = Builder::new().record_trace(true).build();
+
+        sim.host("server", || async {
+            let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+            let mut buf = [0; 8];
+
+            loop {
+                sock.recv_from(&mut buf).await?;
+            }
+        });
+
+        sim.client("client", async {
+            let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+
+            sock.send_to(&[1, 2, 3], "server:1234").await?;
+            crate::partition("client", "server");
+            sock.send_to(&[1, 2, 3], "server:1234").await?;
+
+            Ok(())
+        });
+
+        sim.run()?;
+        sim.crash("server");
+
+        let events = sim
+            .trace()
+            .records()
+            .iter()
+            .filter(|r| !matches!(r.event, Event::Tick(_)))
+            .map(|r| r.event.clone())
+            .collect::<Vec<_>>();
+
+        assert!(matches!(&events[..], [
+            Event::Send(a),
+            Event::Partition(..),
+            Event::Partition(..),
+            Event::Send(b),
+            Event::Drop(c),
+            Event::Crash(_),
+        ] if a.len == 3 && a.kind == "UDP" && a == b && b == c));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pcap() -> Result {
+        let name = format!("turmoil-sim-pcap-{}.pcap", std::process::id());
+        let path = std::env::temp_dir().join(name);
+
+        let mut sim = Builder::new().pcap(&path).build();
+
+        sim.host("server", || async {
+            let listener = TcpListener::bind("0.0.0.0:1234").await?;
+
+            let (mut s, _) = listener.accept().await?;
+            s.read_u32().await?;
+            s.write_all(b"pong").await?;
+
+            future::pending().await
+        });
+
+        sim.client("client", async {
+            let mut s = TcpStream::connect("server:1234").await?;
+            s.write_all(b"ping").await?;
+            s.read_u32().await?;
+
+            Ok(())
+        });
+
+        sim.run()?;
+        drop(sim);
+
+        let pcap = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(&0xa1b2c3d4u32.to_le_bytes(), &pcap[..4]);
+
+        // SYN, then a data segment each way
+        let mut packets = vec![];
+        let mut rest = &pcap[24..];
+        while !rest.is_empty() {
+            let len = u32::from_le_bytes(rest[8..12].try_into()?) as usize;
+            packets.push(&rest[16..16 + len]);
+            rest = &rest[16 + len..];
+        }
+
+        assert_eq!(3, packets.len());
+        assert_eq!(0x02, packets[0][20 + 13]);
+        assert!(packets[1..].iter().all(|p| p.len() == 20 + 20 + 4));
+
+        Ok(())
+    }
+
+    #[test]
+    fn clock_drift() -> Result {
+        let mut sim = Builder::new().build();
+
+        sim.client("fast", async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            Ok(())
+        });
+
+        sim.set_clock_drift("fast", 100_000.0);
+        sim.run()?;
+
+        // The fast clock runs 10% ahead
+        assert_eq!(Duration::from_millis(910), sim.elapsed());
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid clock drift")]
+    fn clock_drift_that_stops_the_clock() {
+        let mut sim = Builder::new().build();
+        sim.client("stopped", async { Ok(()) });
+
+        sim.set_clock_drift("stopped", -1_000_000.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid clock drift")]
+    fn clock_drift_nan() {
+        let mut sim = Builder::new().build();
+        sim.client("nan", async { Ok(()) });
+
+        sim.set_clock_drift("nan", f64::NAN);
+    }
+
+    #[test]
+    fn clock_skew() -> Result {
+        let mut sim = Builder::new().build();
+
+        sim.client("skewed", async {
+            let start = Instant::now();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            assert!(start.elapsed() >= Duration::from_secs(10));
+
+            Ok(())
+        });
+
+        sim.step()?;
+        sim.set_clock_skew("skewed", ClockSkew::Ahead(Duration::from_secs(5)));
+        sim.run()?;
+
+        assert_eq!(Duration::from_millis(5_001), sim.elapsed());
+
+        Ok(())
+    }
+
+    #[test]
+    fn wall_clock() -> Result {
+        let epoch = UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut sim = Builder::new().epoch(epoch).build();
+
+        sim.client("client", async move {
+            assert_eq!(epoch, crate::now());
+
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(epoch + Duration::from_secs(1), crate::now());
+
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            assert_eq!(epoch + Duration::from_secs(12), crate::now());
+
+            Ok(())
+        });
+
+        while sim.elapsed() < Duration::from_millis(1_500) {
+            sim.step()?;
+        }
+        sim.step_wall_clock("client", ClockSkew::Ahead(Duration::from_secs(10)));
+        sim.run()
+    }
 
-        // Tick the networking, processing messages. This is done before
-        // ticking any other runtime, as they might be waiting on network
-        // IO. (It also might be waiting on something else, such as time.)
-        self.world.borrow_mut().topology.tick_by(tick);
+    #[test]
+    fn scheduled_crash_of_client_fails() {
+        let mut sim = Builder::new().build();
 
-        // Tick each host runtimes with running software. If the software
-        // completes, extract the result and return early if an error is
-        // encountered.
-        for (&addr, rt) in self
-            .rts
-            .iter_mut()
-            .filter(|(_, rt)| rt.is_software_running())
-        {
-            let _span_guard = tracing::span!(Level::INFO, "node", name = &*rt.nodename).entered();
+        sim.client("client", async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(())
+        });
 
-            {
-                let mut world = self.world.borrow_mut();
-                // We need to move deliverable messages off the network and
-                // into the dst host. This requires two mutable borrows.
-                let World {
-                    rng,
-                    topology,
-                    hosts,
-                    ..
-                } = world.deref_mut();
-                topology.deliver_messages(rng, hosts.get_mut(&addr).expect("missing host"));
+        let mut schedule = FaultSchedule::new();
+        schedule.crash(Duration::from_millis(500), "client");
+        sim.schedule_faults(schedule);
 
-                // Set the current host (see method docs)
-                world.current = Some(addr);
+        let err = sim.run().unwrap_err();
+        assert!(err.to_string().contains("cannot crash client"), "{err}");
+    }
 
-                world.current_host_mut().now(rt.now());
+    #[test]
+    #[should_panic(expected = "latency window must not end before it starts")]
+    fn inverted_latency_window() {
+        FaultSchedule::new().link_latency(
+            Duration::from_secs(2)..Duration::from_secs(1),
+            "a",
+            "b",
+            Duration::from_millis(500),
+        );
+    }
+
+    #[test]
+    fn fault_schedule() -> Result {
+        let mut sim = Builder::new().record_trace(true).build();
+
+        sim.host("server", || async {
+            let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+            let mut buf = [0; 8];
+
+            loop {
+                sock.recv_from(&mut buf).await?;
             }
+        });
 
-            let is_software_finished = World::enter(&self.world, || rt.tick(tick))?;
+        sim.client("client", async {
+            let sock = UdpSocket::bind("0.0.0.0:1234").await?;
 
-            if rt.is_client() {
-                is_finished = is_finished && is_software_finished;
+            // partitioned, repaired, then slowed down
+            for _ in 0..3 {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                sock.send_to(&[1], "server:1234").await?;
             }
 
-            // Unset the current host
-            let mut world = self.world.borrow_mut();
-            world.current = None;
+            tokio::time::sleep(Duration::from_secs(3)).await;
 
-            world.tick(addr, tick);
-        }
+            Ok(())
+        });
 
-        self.elapsed += tick;
+        let mut schedule = FaultSchedule::new();
+        schedule
+            .crash(Duration::from_secs(5), "server")
+            .partition(Duration::from_millis(500), "client", "server")
+            .repair(Duration::from_millis(1_500), "client", "server")
+            .link_latency(
+                Duration::from_millis(2_500)..Duration::from_millis(4_000),
+                "client",
+                "server",
+                Duration::from_millis(500),
+            );
+        sim.schedule_faults(schedule);
 
-        if self.elapsed > self.config.duration && !is_finished {
-            return Err(format!(
-                "Ran for {:?} without completing",
-                self.config.duration
-            ))?;
-        }
+        sim.run()?;
 
-        Ok(is_finished)
+        let events = sim
+            .trace()
+            .records()
+            .iter()
+            .filter(|r| !matches!(r.event, Event::Tick(_) | Event::Send(_)))
+            .map(|r| (r.elapsed.as_millis(), r.event.clone()))
+            .collect::<Vec<_>>();
+
+        assert!(matches!(&events[..], [
+            (500, Event::Partition(..)),
+            (500, Event::Partition(..)),
+            (1_000, Event::Drop(_)),
+            (1_500, Event::Repair(..)),
+            (1_500, Event::Repair(..)),
+            (2_000..=2_100, Event::Deliver(_)),
+            (2_500, Event::LinkLatency(_, _, latency)),
+            (2_500, Event::LinkLatency(..)),
+            (3_500..=3_600, Event::Deliver(_)),
+            (4_000, Event::RestoreLinkLatency(..)),
+            (4_000, Event::RestoreLinkLatency(..)),
+            (5_000, Event::Crash(_)),
+        ] if *latency == Duration::from_millis(500)));
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod test {
-    use std::{
-        net::{IpAddr, Ipv4Addr},
-        rc::Rc,
-        sync::{
-            atomic::{AtomicU64, Ordering},
-            Arc,
-        },
-        time::Duration,
-    };
+    #[test]
+    fn nemesis() -> Result {
+        let mut nemesis = Nemesis::new();
+        nemesis
+            .crash(0.01, Duration::from_millis(10)..=Duration::from_millis(50))
+            .partition(0.01, Duration::from_millis(10)..=Duration::from_millis(50))
+            .hold(0.01, Duration::from_millis(10)..=Duration::from_millis(50));
+
+        let mut builder = Builder::new();
+        builder.seed(7).nemesis(nemesis);
+
+        builder.check_determinism(|sim| {
+            for host in ["a", "b", "c"] {
+                sim.host(host, || async { future::pending().await });
+            }
 
-    use std::future;
-    use tokio::{
-        io::{AsyncReadExt, AsyncWriteExt},
-        sync::Semaphore,
-        time::Instant,
-    };
+            sim.client("client", async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                Ok(())
+            });
 
-    use crate::{
-        elapsed, hold,
-        net::{TcpListener, TcpStream},
-        Builder, Result,
-    };
+            sim.run()?;
 
-    #[test]
-    fn client_error() {
-        let mut sim = Builder::new().build();
+            let client = sim.lookup("client");
+            let mut crashed = None;
+            let (mut crashes, mut partitions, mut holds) = (0, 0, 0);
+
+            for record in sim.trace().records() {
+                match record.event {
+                    Event::Crash(addr) => {
+                        assert_eq!(None, crashed.replace(addr));
+                        crashes += 1;
+                    }
+                    Event::Bounce(addr) => assert_eq!(Some(addr), crashed.take()),
+                    Event::Partition(a, b) => {
+                        assert!(a != client && b != client);
+                        partitions += 1;
+                    }
+                    Event::HoldLink(..) => holds += 1,
+                    _ => {}
+                }
+            }
 
-        sim.client("doomed", async { Err("An Error")? });
+            assert!(crashes > 0);
+            assert!(partitions > 0);
+            assert!(holds > 0);
 
-        assert!(sim.run().is_err());
+            Ok(())
+        })
     }
 
     #[test]
@@ -597,6 +1633,7 @@ mod test {
         let mut sim = Builder::new()
             .min_message_latency(global)
             .max_message_latency(global)
+            .tcp_connect_timeout(Duration::from_secs(1))
             .build();
 
         sim.host("server", || async {
@@ -693,6 +1730,205 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn links_are_created_on_first_send() -> Result {
+        let mut sim = Builder::new().build();
+
+        sim.client("a", async {
+            let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+            // Sent once "b" is bound, in case the latency rolled is zero
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            sock.send_to(&[1], "b:1234").await?;
+
+            Ok(())
+        });
+
+        sim.client("b", async {
+            let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+            sock.recv_from(&mut [0; 1]).await?;
+
+            Ok(())
+        });
+
+        sim.client("c", async { Ok(()) });
+
+        sim.run()?;
+
+        let (a, b, c) = (sim.lookup("a"), sim.lookup("b"), sim.lookup("c"));
+
+        let mut pairs = vec![];
+        sim.links(|links| pairs.extend(links.map(|link| link.pair())));
+        assert_eq!(vec![(a, b)], pairs);
+
+        let stats = sim.stats();
+        assert_eq!(1, stats.link(a, b).unwrap().messages_delivered);
+        assert!(stats.link(a, c).is_none());
+
+        Ok(())
+    }
+
+    /// Runs a simulation where "a" and "b" each send a datagram to "c" with
+    /// the given latencies, returning the order in which they arrive.
+    fn arrival_order(seed: u64, a: Duration, b: Duration) -> Result<Vec<&'static str>> {
+        let mut sim = Builder::new().seed(seed).build();
+        let received = Rc::new(std::cell::RefCell::new(vec![]));
+
+        let log = received.clone();
+        sim.client("c", async move {
+            let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+            for _ in 0..2 {
+                let (_, origin) = sock.recv_from(&mut [0; 1]).await?;
+                log.borrow_mut().push(origin.ip());
+            }
+
+            Ok(())
+        });
+
+        for host in ["a", "b"] {
+            sim.client(host, async {
+                let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+                sock.send_to(&[0], "c:1234").await?;
+
+                Ok(())
+            });
+        }
+
+        sim.set_link_latency("a", "c", a);
+        sim.set_link_latency("b", "c", b);
+        sim.run()?;
+
+        let a = sim.lookup("a");
+        let received = received.borrow().clone();
+        Ok(received
+            .into_iter()
+            .map(|ip| if ip == a { "a" } else { "b" })
+            .collect())
+    }
+
+    #[test]
+    fn deliver_in_due_order_across_links() -> Result {
+        // Both messages are due within the same tick
+        let order = arrival_order(0, Duration::from_micros(2900), Duration::from_micros(2100))?;
+        assert_eq!(vec!["b", "a"], order);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deliver_ties_in_seeded_order() -> Result {
+        let latency = Duration::from_millis(2);
+        let orders = (0..16)
+            .map(|seed| arrival_order(seed, latency, latency))
+            .collect::<Result<Vec<_>>>()?;
+
+        assert!(orders.iter().any(|order| order != &orders[0]));
+        assert_eq!(orders[3], arrival_order(3, latency, latency)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn random_host_order() -> Result {
+        fn run(seed: u64, random_host_order: bool) -> Result<Vec<&'static str>> {
+            let mut sim = Builder::new()
+                .seed(seed)
+                .random_host_order(random_host_order)
+                .build();
+            let ran = Rc::new(std::cell::RefCell::new(vec![]));
+
+            for host in ["a", "b", "c"] {
+                let log = ran.clone();
+                sim.client(host, async move {
+                    log.borrow_mut().push(host);
+                    Ok(())
+                });
+            }
+
+            sim.run()?;
+
+            let ran = ran.borrow().clone();
+            Ok(ran)
+        }
+
+        for seed in 0..8 {
+            assert_eq!(vec!["a", "b", "c"], run(seed, false)?);
+        }
+
+        let orders = (0..8)
+            .map(|seed| run(seed, true))
+            .collect::<Result<Vec<_>>>()?;
+        assert!(orders.iter().any(|order| order != &orders[0]));
+        assert_eq!(orders[5], run(5, true)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn manipulate_sent_messages() -> Result {
+        let mut sim = Builder::new().build();
+
+        sim.client("server", async {
+            let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+            let mut buf = [0; 1];
+
+            let start = elapsed();
+            for i in [2, 1] {
+                sock.recv_from(&mut buf).await?;
+                assert_eq!(i, buf[0]);
+            }
+            assert!(elapsed() - start >= Duration::from_secs(1));
+
+            Ok(())
+        });
+
+        sim.client("client", async {
+            let sock = UdpSocket::bind("0.0.0.0:0").await?;
+            for i in 0..3 {
+                sock.send_to(&[i], "server:1234").await?;
+            }
+
+            Ok(())
+        });
+
+        sim.hold("client", "server");
+        sim.step()?;
+
+        sim.links(|mut links| {
+            let link = links.next().unwrap();
+            assert_eq!((LinkState::Held, LinkState::Held), link.state());
+            assert_eq!(3, link.len());
+
+            for (i, sent) in link.enumerate() {
+                assert!(sent.is_held());
+                assert_eq!(None, sent.scheduled_at());
+                assert_eq!(Some(&[i as u8][..]), sent.payload().map(|p| &p[..]));
+
+                match i {
+                    0 => sent.drop(),
+                    1 => sent.delay(Duration::from_secs(1)),
+                    _ => sent.deliver(),
+                }
+            }
+        });
+
+        sim.links(|mut links| {
+            let link = links.next().unwrap();
+            assert_eq!(2, link.len());
+
+            let at = link.map(|sent| sent.scheduled_at()).collect::<Vec<_>>();
+            assert_eq!(
+                vec![
+                    Some(sim.elapsed() + Duration::from_secs(1)),
+                    Some(sim.elapsed())
+                ],
+                at
+            );
+        });
+
+        sim.repair("client", "server");
+        sim.run()
+    }
+
     /// This is a regression test that ensures JoinError::Cancelled is not
     /// propagated to the test when the host crashes, which was causing
     /// incorrect test failure.
@@ -886,4 +2122,201 @@ mod test {
 
         sim.run()
     }
+
+    #[test]
+    fn event_driven_matches_ticking() -> Result {
+        type Log = Vec<(&'static str, Duration, Duration)>;
+
+        fn run(event_driven: bool) -> Result<(Log, u32)> {
+            let mut sim = Builder::new()
+                .event_driven(event_driven)
+                .min_message_latency(Duration::from_millis(10))
+                .max_message_latency(Duration::from_millis(10))
+                .latency_distribution(crate::LatencyDistribution::Fixed(Duration::from_millis(10)))
+                .simulation_duration(Duration::from_secs(30))
+                .build();
+
+            // When each host received a message or its timer fired, in
+            // simulated time and by its own `Instant`
+            let received = Rc::new(std::cell::RefCell::new(vec![]));
+
+            let log = received.clone();
+            sim.host("server", move || {
+                let log = log.clone();
+                async move {
+                    let start = Instant::now();
+                    let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+                    let mut buf = [0; 8];
+
+                    // A timer that is pending while messages arrive
+                    let timer = log.clone();
+                    tokio::task::spawn_local(async move {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        timer
+                            .borrow_mut()
+                            .push(("timer", elapsed(), start.elapsed()));
+                    });
+
+                    loop {
+                        let (n, origin) = sock.recv_from(&mut buf).await?;
+                        log.borrow_mut().push(("recv", elapsed(), start.elapsed()));
+                        tokio::time::sleep(Duration::from_millis(250)).await;
+                        log.borrow_mut().push(("sleep", elapsed(), start.elapsed()));
+                        sock.send_to(&buf[..n], origin).await?;
+                    }
+                }
+            });
+
+            let log = received.clone();
+            sim.client("client", async move {
+                let start = Instant::now();
+                let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+                let mut buf = [0; 8];
+
+                for i in 0..5u8 {
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                    log.borrow_mut().push(("sleep", elapsed(), start.elapsed()));
+                    sock.send_to(&[i], "server:1234").await?;
+                    sock.recv_from(&mut buf).await?;
+                    log.borrow_mut().push(("recv", elapsed(), start.elapsed()));
+                }
+
+                Ok(())
+            });
+
+            let mut steps = 0;
+            while !sim.step()? {
+                steps += 1;
+            }
+
+            let received = received.borrow().clone();
+            Ok((received, steps))
+        }
+
+        let (ticking, ticking_steps) = run(false)?;
+        let (event_driven, event_driven_steps) = run(true)?;
+
+        assert_eq!(21, ticking.len());
+        assert_eq!(ticking, event_driven);
+        assert!(event_driven_steps * 5 < ticking_steps);
+
+        Ok(())
+    }
+
+    #[test]
+    fn skip_idle_hosts_matches_ticking() -> Result {
+        fn run(skip_idle_hosts: bool) -> Result<(Vec<(Duration, Duration)>, usize)> {
+            let mut sim = Builder::new()
+                .skip_idle_hosts(skip_idle_hosts)
+                .min_message_latency(Duration::from_millis(10))
+                .max_message_latency(Duration::from_millis(10))
+                .latency_distribution(crate::LatencyDistribution::Fixed(Duration::from_millis(10)))
+                .seed(7)
+                .build();
+
+            let received = Rc::new(std::cell::RefCell::new(vec![]));
+
+            for host in ["server-a", "server-b"] {
+                let log = received.clone();
+                sim.host(host, move || {
+                    let log = log.clone();
+                    async move {
+                        let start = Instant::now();
+                        let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+                        let mut buf = [0; 8];
+
+                        loop {
+                            let (n, origin) = sock.recv_from(&mut buf).await?;
+                            log.borrow_mut().push((elapsed(), start.elapsed()));
+                            tokio::time::sleep(Duration::from_millis(250)).await;
+                            log.borrow_mut().push((elapsed(), start.elapsed()));
+                            sock.send_to(&buf[..n], origin).await?;
+                        }
+                    }
+                });
+            }
+
+            let log = received.clone();
+            sim.client("client", async move {
+                let start = Instant::now();
+                let sock = UdpSocket::bind("0.0.0.0:1234").await?;
+                let mut buf = [0; 8];
+
+                for i in 0..3u8 {
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                    sock.send_to(&[i], "server-a:1234").await?;
+                    sock.recv_from(&mut buf).await?;
+                    sock.send_to(&[i], "server-b:1234").await?;
+                    sock.recv_from(&mut buf).await?;
+                    log.borrow_mut().push((elapsed(), start.elapsed()));
+                }
+
+                Ok(())
+            });
+
+            sim.run()?;
+
+            let received = received.borrow().clone();
+            let skipped = sim.rts.values().map(|rt| rt.skipped).sum();
+            Ok((received, skipped))
+        }
+
+        let (ticking, skipped) = run(false)?;
+        assert_eq!(0, skipped);
+
+        let (skipping, skipped) = run(true)?;
+        assert!(skipped > 0);
+
+        assert_eq!(15, ticking.len());
+        assert_eq!(ticking, skipping);
+
+        Ok(())
+    }
+
+    #[test]
+    fn skip_idle_hosts_keeps_instant_across_accept() -> Result {
+        fn run(skip_idle_hosts: bool) -> Result<Vec<(Duration, Duration)>> {
+            let mut sim = Builder::new()
+                .skip_idle_hosts(skip_idle_hosts)
+                .min_message_latency(Duration::from_millis(5))
+                .max_message_latency(Duration::from_millis(5))
+                .latency_distribution(crate::LatencyDistribution::Fixed(Duration::from_millis(5)))
+                .build();
+
+            sim.host("server", || async {
+                let listener = TcpListener::bind("0.0.0.0:1234").await?;
+                tokio::time::sleep(Duration::from_millis(7)).await;
+
+                let (_s, _) = listener.accept().await?;
+                future::pending().await
+            });
+
+            let log = Rc::new(std::cell::RefCell::new(vec![]));
+            let client_log = log.clone();
+            sim.client("client", async move {
+                let start = Instant::now();
+
+                let _s = TcpStream::connect("server:1234").await?;
+                client_log.borrow_mut().push((elapsed(), start.elapsed()));
+
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                client_log.borrow_mut().push((elapsed(), start.elapsed()));
+
+                Ok(())
+            });
+
+            sim.run()?;
+
+            let log = log.borrow().clone();
+            Ok(log)
+        }
+
+        let ticking = run(false)?;
+        let skipping = run(true)?;
+
+        assert_eq!(2, ticking.len());
+        assert_eq!(ticking, skipping);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,279 @@
+use indexmap::IndexMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Network counters accumulated over the course of a simulation.
+///
+/// Link counters are kept per direction, for messages sent from one host to
+/// another. Host counters track what each host sent and received.
+///
+/// ```
+/// let mut sim = turmoil::Builder::new().build();
+///
+/// // ... run the simulation
+///
+/// let stats = sim.stats();
+/// assert_eq!(0, stats.network().messages_sent);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub(crate) links: IndexMap<(IpAddr, IpAddr), LinkStats>,
+    pub(crate) hosts: IndexMap<IpAddr, HostStats>,
+}
+
+/// Counters for messages sent from one host to another.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    /// Messages sent onto the link.
+    pub messages_sent: u64,
+
+    /// Payload bytes sent onto the link.
+    pub bytes_sent: u64,
+
+    /// Messages delivered to the destination host.
+    pub messages_delivered: u64,
+
+    /// Payload bytes delivered to the destination host.
+    pub bytes_delivered: u64,
+
+    /// Messages dropped by a partition, a network filter or test code.
+    pub dropped: u64,
+
+    /// Messages lost by a lossy, but otherwise healthy, link.
+    pub lost: u64,
+
+    /// Messages held by the link.
+    pub held: u64,
+
+    /// RSTs sent in response to TCP segments that could not be delivered.
+    pub rsts: u64,
+
+    /// How long messages spent on the link before arriving at the
+    /// destination host.
+    pub latency: Histogram,
+}
+
+/// Counters for the messages a host sent and received.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostStats {
+    /// Messages sent onto the network.
+    pub messages_sent: u64,
+
+    /// Payload bytes sent onto the network.
+    pub bytes_sent: u64,
+
+    /// Messages received from the network.
+    pub messages_received: u64,
+
+    /// Payload bytes received from the network.
+    pub bytes_received: u64,
+
+    /// UDP datagrams dropped because the receiving socket's buffer was full.
+    pub udp_dropped_full: u64,
+
+    /// RSTs generated in response to TCP segments that could not be
+    /// delivered.
+    pub rsts: u64,
+}
+
+/// A histogram of durations, with exponentially sized buckets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Histogram {
+    /// Bucket `i` counts durations less than 2^i microseconds that do not fit
+    /// in a smaller bucket.
+    buckets: Vec<u64>,
+    count: u64,
+    sum: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl Stats {
+    /// Counters for messages sent from `from` to `to`, or `None` if the link
+    /// between them has never been used. Links are created when one host
+    /// first sends a message to the other, or the link is configured.
+    pub fn link(&self, from: IpAddr, to: IpAddr) -> Option<&LinkStats> {
+        self.links.get(&(from, to))
+    }
+
+    /// Counters for each direction of each link, as `(from, to, stats)`.
+    pub fn links(&self) -> impl Iterator<Item = (IpAddr, IpAddr, &LinkStats)> {
+        self.links
+            .iter()
+            .map(|(&(from, to), stats)| (from, to, stats))
+    }
+
+    /// Counters for the host at `addr`.
+    pub fn host(&self, addr: IpAddr) -> Option<&HostStats> {
+        self.hosts.get(&addr)
+    }
+
+    /// Counters for each host.
+    pub fn hosts(&self) -> impl Iterator<Item = (IpAddr, &HostStats)> {
+        self.hosts.iter().map(|(&addr, stats)| (addr, stats))
+    }
+
+    /// Counters summed across all links.
+    pub fn network(&self) -> LinkStats {
+        let mut total = LinkStats::default();
+        for stats in self.links.values() {
+            total.merge(stats);
+        }
+        total
+    }
+}
+
+impl LinkStats {
+    pub(crate) fn merge(&mut self, other: &LinkStats) {
+        self.messages_sent += other.messages_sent;
+        self.bytes_sent += other.bytes_sent;
+        self.messages_delivered += other.messages_delivered;
+        self.bytes_delivered += other.bytes_delivered;
+        self.dropped += other.dropped;
+        self.lost += other.lost;
+        self.held += other.held;
+        self.rsts += other.rsts;
+        self.latency.merge(&other.latency);
+    }
+}
+
+impl Histogram {
+    /// Record a single duration.
+    pub(crate) fn record(&mut self, value: Duration) {
+        let i = (u128::BITS - value.as_micros().leading_zeros()) as usize;
+        if self.buckets.len() <= i {
+            self.buckets.resize(i + 1, 0);
+        }
+        self.buckets[i] += 1;
+
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        if other.count == 0 {
+            return;
+        }
+
+        if self.buckets.len() < other.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        for (bucket, n) in self.buckets.iter_mut().zip(&other.buckets) {
+            *bucket += n;
+        }
+
+        self.min = if self.count == 0 {
+            other.min
+        } else {
+            self.min.min(other.min)
+        };
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+
+    /// The number of recorded durations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The smallest recorded duration.
+    pub fn min(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// The largest recorded duration.
+    pub fn max(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// The mean of the recorded durations.
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0)
+            .then(|| Duration::from_nanos((self.sum.as_nanos() / self.count as u128) as u64))
+    }
+
+    /// An upper bound for the `q` quantile, e.g. `0.99`, of the recorded
+    /// durations. Accurate to within a factor of two.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        assert!((0.0..=1.0).contains(&q), "quantile must be between 0 and 1");
+
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = ((q * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (upper, n) in self.buckets() {
+            seen += n;
+            if seen >= rank {
+                return Some(upper.min(self.max));
+            }
+        }
+
+        Some(self.max)
+    }
+
+    /// The non-empty buckets, as the exclusive upper bound of each bucket and
+    /// the number of durations in it.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, &n)| n > 0)
+            .map(|(i, &n)| (Duration::from_micros(1 << i), n))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Histogram;
+    use std::time::Duration;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = Histogram::default();
+        assert_eq!(None, histogram.quantile(0.5));
+
+        for ms in [1, 2, 3, 4, 100] {
+            histogram.record(Duration::from_millis(ms));
+        }
+
+        assert_eq!(5, histogram.count());
+        assert_eq!(Some(Duration::from_millis(1)), histogram.min());
+        assert_eq!(Some(Duration::from_millis(100)), histogram.max());
+        assert_eq!(Some(Duration::from_millis(22)), histogram.mean());
+
+        // 3 and 4ms share the bucket below 4.096ms
+        assert_eq!(Some(Duration::from_micros(4096)), histogram.quantile(0.5));
+        assert_eq!(Some(Duration::from_millis(100)), histogram.quantile(1.0));
+
+        let buckets = histogram.buckets().collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (Duration::from_micros(1024), 1),
+                (Duration::from_micros(2048), 1),
+                (Duration::from_micros(4096), 2),
+                (Duration::from_micros(131072), 1),
+            ],
+            buckets
+        );
+    }
+
+    #[test]
+    fn histogram_mean_with_large_count() {
+        let histogram = Histogram {
+            count: u32::MAX as u64 + 2,
+            sum: Duration::from_secs(u32::MAX as u64 + 2),
+            ..Default::default()
+        };
+
+        assert_eq!(Some(Duration::from_secs(1)), histogram.mean());
+    }
+}
@@ -1,11 +1,14 @@
-use crate::envelope::{Envelope, Protocol};
+use crate::envelope::{Datagram, Envelope, Protocol, Segment};
 use crate::host::Host;
 use crate::rt::Rt;
-use crate::{config, TRACING_TARGET};
+use crate::stats::{LinkStats, Stats};
+use crate::trace::{Event, Recorder};
+use crate::{config, CorruptionKind, LatencyDistribution, TRACING_TARGET};
 
-use indexmap::IndexMap;
+use bytes::Bytes;
+
+use indexmap::{IndexMap, IndexSet};
 use rand::{Rng, RngCore};
-use rand_distr::{Distribution, Exp};
 use std::collections::VecDeque;
 use std::io::{Error, ErrorKind, Result};
 use std::net::{IpAddr, SocketAddr};
@@ -16,13 +19,74 @@ use tokio::time::Instant;
 pub(crate) struct Topology {
     config: config::Link,
 
-    /// Specific configuration overrides between specific hosts.
+    /// Links between pairs of hosts, created when first used, e.g. when one
+    /// host first sends a message to the other.
     links: IndexMap<Pair, Link>,
 
+    /// Registered hosts, each with an index of the links to it that decide
+    /// when it may next receive a message.
+    hosts: IndexMap<IpAddr, Peers>,
+
+    /// Links with messages in flight or on hold, which are the only ones that
+    /// need to be ticked.
+    busy: IndexSet<Pair>,
+
+    /// Messages that are ready to be delivered, by destination host.
+    deliverable: Deliverables,
+
     /// We don't use a Rt for async. Right now, we just use it to tick time
     /// forward in the same way we do it elsewhere. We'd like to represent
     /// network state with async in the future.
     rt: Rt<'static>,
+
+    /// Decides what happens to each message sent onto the network, if set.
+    filter: Option<NetworkFilter>,
+
+    /// When the simulation started, in network time.
+    start: Instant,
+}
+
+pub(crate) type NetworkFilter = Box<dyn FnMut(&Envelope) -> Verdict>;
+
+/// Messages that are ready to be delivered, by destination host.
+type Deliverables = IndexMap<IpAddr, Vec<Deliverable>>;
+
+/// A message that is ready to be delivered.
+struct Deliverable {
+    /// When the message was due to be delivered.
+    at: Instant,
+
+    envelope: Envelope,
+}
+
+/// What happens to a message, as decided by a network filter.
+///
+/// See [`Sim::set_network_filter`](crate::Sim::set_network_filter).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Send the message as usual.
+    Deliver,
+
+    /// Drop the message.
+    Drop,
+
+    /// Send the message as usual, delivering it after additional latency.
+    Delay(Duration),
+
+    /// Hold the message on the link until it is released, either with
+    /// [`Sim::release`](crate::Sim::release) or [`SentRef::deliver`].
+    Hold,
+
+    /// Send the message twice. Only UDP datagrams are duplicated, TCP segments
+    /// are sent once.
+    Duplicate,
+
+    /// Replace the message payload before sending it. Only UDP datagrams and
+    /// TCP data segments carry a payload, other messages are sent unchanged.
+    ///
+    /// TCP segments keep their length: a shorter payload is padded with zeros,
+    /// and a longer one is cut short.
+    Rewrite(Bytes),
 }
 
 /// This type is used as the key in the [`Topology::links`] map. See [`new`]
@@ -46,6 +110,8 @@ impl Pair {
 /// An iterator for the network topology, providing access to all active links
 /// in the simulated network.
 pub struct LinksIter<'a> {
+    now: Instant,
+    start: Instant,
     iter: indexmap::map::IterMut<'a, Pair, Link>,
 }
 
@@ -54,10 +120,26 @@ pub struct LinksIter<'a> {
 pub struct LinkIter<'a> {
     a: IpAddr,
     b: IpAddr,
+    state: (LinkState, LinkState),
     now: Instant,
+    start: Instant,
+    remaining: usize,
     iter: std::collections::vec_deque::IterMut<'a, Sent>,
 }
 
+/// The state of one direction of a link.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinkState {
+    /// Messages are sent as usual.
+    Healthy,
+
+    /// Messages are dropped, due to an explicit or random partition.
+    Partitioned,
+
+    /// Messages are held until they are released.
+    Held,
+}
+
 impl<'a> LinkIter<'a> {
     /// The [`IpAddr`] pair for the link. Always ordered to uniquely identify
     /// the link.
@@ -65,6 +147,23 @@ impl<'a> LinkIter<'a> {
         (self.a, self.b)
     }
 
+    /// The state of each direction of the link, for messages sent from the
+    /// first host in [`pair`](LinkIter::pair) to the second, and from the
+    /// second to the first.
+    pub fn state(&self) -> (LinkState, LinkState) {
+        self.state
+    }
+
+    /// The number of remaining messages on the link.
+    pub fn len(&self) -> usize {
+        self.remaining
+    }
+
+    /// Whether there are no remaining messages on the link.
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+
     /// Schedule all messages on the link for delivery the next time the
     /// simulation steps, consuming the iterator.
     pub fn deliver_all(self) {
@@ -80,6 +179,7 @@ pub struct SentRef<'a> {
     src: SocketAddr,
     dst: SocketAddr,
     now: Instant,
+    start: Instant,
     sent: &'a mut Sent,
 }
 
@@ -94,11 +194,46 @@ impl<'a> SentRef<'a> {
         &self.sent.protocol
     }
 
+    /// The message payload, for UDP datagrams and TCP data segments.
+    pub fn payload(&self) -> Option<&Bytes> {
+        self.sent.protocol.payload()
+    }
+
+    /// Whether the message is held on the link.
+    pub fn is_held(&self) -> bool {
+        matches!(self.sent.status, DeliveryStatus::Hold)
+    }
+
+    /// The simulated time at which the message is scheduled for delivery,
+    /// comparable with [`Sim::elapsed`](crate::Sim::elapsed), or `None` if the
+    /// message is held.
+    pub fn scheduled_at(&self) -> Option<Duration> {
+        match self.sent.status {
+            DeliveryStatus::DeliverAfter(at) => Some(at.saturating_duration_since(self.start)),
+            _ => None,
+        }
+    }
+
     /// Schedule the message for delivery the next time the simulation steps,
     /// consuming the item.
     pub fn deliver(self) {
         self.sent.deliver(self.now);
     }
+
+    /// Push back delivery of the message by `duration`, consuming the item. A
+    /// held message is released, and delivered once `duration` has passed.
+    pub fn delay(self, duration: Duration) {
+        let at = match self.sent.status {
+            DeliveryStatus::DeliverAfter(at) => at,
+            _ => self.now,
+        };
+        self.sent.deliver(at + duration);
+    }
+
+    /// Drop the message, so that it is never delivered, consuming the item.
+    pub fn drop(self) {
+        self.sent.status = DeliveryStatus::Dropped;
+    }
 }
 
 impl<'a> Iterator for LinksIter<'a> {
@@ -110,7 +245,13 @@ impl<'a> Iterator for LinksIter<'a> {
         Some(LinkIter {
             a: pair.0,
             b: pair.1,
-            now: link.now,
+            state: (
+                link.directions[direction(pair.0, pair.1)].link_state(),
+                link.directions[direction(pair.1, pair.0)].link_state(),
+            ),
+            now: self.now,
+            start: self.start,
+            remaining: link.sent.len(),
             iter: link.sent.iter_mut(),
         })
     }
@@ -121,34 +262,66 @@ impl<'a> Iterator for LinkIter<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let sent = self.iter.next()?;
+        self.remaining -= 1;
 
         Some(SentRef {
             src: sent.src,
             dst: sent.dst,
             now: self.now,
+            start: self.start,
             sent,
         })
     }
 }
 
+/// Links to a host, indexed by peer.
+#[derive(Default)]
+struct Peers {
+    /// Peers with messages in flight or on hold on the link with the host.
+    busy: IndexSet<IpAddr>,
+
+    /// Peers whose direction of the link to the host overrides the latency.
+    latency: IndexSet<IpAddr>,
+}
+
 /// A two-way link between two hosts on the network.
 struct Link {
-    state: State,
-
-    /// Optional, per-link configuration.
-    config: config::Link,
+    /// State and configuration for each direction of the link, indexed with
+    /// [`direction`].
+    directions: [Direction; 2],
 
     /// Sent messages that are either scheduled for delivery in the future
     /// or are on hold.
     sent: VecDeque<Sent>,
 
-    /// Messages that are ready to be delivered.
-    deliverable: IndexMap<IpAddr, VecDeque<Envelope>>,
-
-    /// The current network time, moved forward with [`Link::tick`].
+    /// The current network time, moved forward with [`Link::tick`] while the
+    /// link is busy, and caught up whenever the link is used.
     now: Instant,
 }
 
+/// One direction of a [`Link`], carrying messages from one host to the other.
+struct Direction {
+    state: State,
+
+    /// Optional, per-direction configuration.
+    config: config::Link,
+
+    /// When the last message finishes being put on the link, if bandwidth is
+    /// limited.
+    busy_until: Option<Instant>,
+
+    /// Whether the burst loss model is in the bad state.
+    bursting: bool,
+
+    /// Counters for messages sent in this direction.
+    stats: LinkStats,
+}
+
+/// Index into [`Link::directions`] for messages sent from `src` to `dst`.
+fn direction(src: IpAddr, dst: IpAddr) -> usize {
+    (src > dst) as usize
+}
+
 enum State {
     /// The link is healthy.
     Healthy,
@@ -165,47 +338,131 @@ enum State {
 
 impl Topology {
     pub(crate) fn new(config: config::Link) -> Topology {
+        let rt = Rt::no_software();
+        let start = rt.now();
+
         Topology {
             config,
             links: IndexMap::new(),
-            rt: Rt::no_software(),
+            hosts: IndexMap::new(),
+            busy: IndexSet::new(),
+            deliverable: IndexMap::new(),
+            rt,
+            filter: None,
+            start,
         }
     }
 
-    /// Register a link between two hosts
-    pub(crate) fn register(&mut self, a: IpAddr, b: IpAddr) {
-        let pair = Pair::new(a, b);
-        assert!(self.links.insert(pair, Link::new(self.rt.now())).is_none());
+    /// Register a host. Links to other hosts are created when first used.
+    pub(crate) fn register(&mut self, addr: IpAddr) {
+        assert!(self.hosts.insert(addr, Peers::default()).is_none());
+    }
+
+    /// The link between two hosts, created if this is its first use.
+    fn link(&mut self, a: IpAddr, b: IpAddr) -> &mut Link {
+        let now = self.rt.now();
+        let link = self
+            .links
+            .entry(Pair::new(a, b))
+            .or_insert_with(|| Link::new(now));
+        link.now = now;
+        link
+    }
+
+    /// The latency override for the direction of the link, created from the
+    /// global configuration if there is none.
+    fn link_latency_mut(&mut self, from: IpAddr, to: IpAddr) -> &mut config::Latency {
+        if let Some(peers) = self.hosts.get_mut(&to) {
+            peers.latency.insert(from);
+        }
+
+        let global = self.config.latency().clone();
+        self.direction(from, to).latency(&global)
     }
 
     pub(crate) fn set_max_message_latency(&mut self, value: Duration) {
         self.config.latency_mut().max_message_latency = value;
     }
 
-    pub(crate) fn set_link_message_latency(&mut self, a: IpAddr, b: IpAddr, value: Duration) {
-        let latency = self.links[&Pair::new(a, b)].latency(self.config.latency());
+    // Per link configuration and state applies to messages sent from `from` to
+    // `to`. Callers apply two-way changes to both directions.
+
+    pub(crate) fn set_link_message_latency(&mut self, from: IpAddr, to: IpAddr, value: Duration) {
+        let latency = self.link_latency_mut(from, to);
         latency.min_message_latency = value;
         latency.max_message_latency = value;
     }
 
-    pub(crate) fn set_link_max_message_latency(&mut self, a: IpAddr, b: IpAddr, value: Duration) {
-        self.links[&Pair::new(a, b)]
-            .latency(self.config.latency())
-            .max_message_latency = value;
+    /// The latency override for the direction of the link, if any.
+    pub(crate) fn link_latency(&mut self, from: IpAddr, to: IpAddr) -> Option<config::Latency> {
+        self.direction(from, to).config.latency.clone()
+    }
+
+    /// Replace the latency override for the direction of the link. `None`
+    /// reverts it to the global configuration.
+    pub(crate) fn set_link_latency(
+        &mut self,
+        from: IpAddr,
+        to: IpAddr,
+        latency: Option<config::Latency>,
+    ) {
+        if let Some(peers) = self.hosts.get_mut(&to) {
+            peers.latency.insert(from);
+        }
+
+        self.direction(from, to).config.latency = latency;
+    }
+
+    pub(crate) fn set_link_max_message_latency(
+        &mut self,
+        from: IpAddr,
+        to: IpAddr,
+        value: Duration,
+    ) {
+        self.link_latency_mut(from, to).max_message_latency = value;
+    }
+
+    pub(crate) fn set_link_latency_distribution(
+        &mut self,
+        from: IpAddr,
+        to: IpAddr,
+        value: LatencyDistribution,
+    ) {
+        value.validate();
+        self.link_latency_mut(from, to).latency_distribution = value;
     }
 
     pub(crate) fn set_message_latency_curve(&mut self, value: f64) {
-        self.config.latency_mut().latency_distribution = Exp::new(value).unwrap();
+        self.set_latency_distribution(LatencyDistribution::Exponential(value));
+    }
+
+    pub(crate) fn set_latency_distribution(&mut self, value: LatencyDistribution) {
+        value.validate();
+        self.config.latency_mut().latency_distribution = value;
     }
 
     pub(crate) fn set_fail_rate(&mut self, value: f64) {
         self.config.message_loss_mut().fail_rate = value;
     }
 
-    pub(crate) fn set_link_fail_rate(&mut self, a: IpAddr, b: IpAddr, value: f64) {
-        self.links[&Pair::new(a, b)]
-            .message_loss(self.config.message_loss())
-            .fail_rate = value;
+    pub(crate) fn set_link_fail_rate(&mut self, from: IpAddr, to: IpAddr, value: f64) {
+        let global = self.config.message_loss().clone();
+        self.direction(from, to).message_loss(&global).fail_rate = value;
+    }
+
+    pub(crate) fn set_link_loss_rate(&mut self, from: IpAddr, to: IpAddr, value: f64) {
+        let global = self.config.message_loss().clone();
+        self.direction(from, to).message_loss(&global).loss_rate = value;
+    }
+
+    pub(crate) fn set_link_burst_loss(
+        &mut self,
+        from: IpAddr,
+        to: IpAddr,
+        value: Option<config::BurstLoss>,
+    ) {
+        let global = self.config.message_loss().clone();
+        self.direction(from, to).message_loss(&global).burst_loss = value;
     }
 
     // Send a `message` from `src` to `dst`. This method returns immediately,
@@ -214,12 +471,23 @@ impl Topology {
     pub(crate) fn enqueue_message(
         &mut self,
         rand: &mut dyn RngCore,
+        trace: &mut Recorder,
         src: SocketAddr,
         dst: SocketAddr,
         message: Protocol,
     ) -> Result<()> {
-        if let Some(link) = self.links.get_mut(&Pair::new(src.ip(), dst.ip())) {
-            link.enqueue_message(&self.config, rand, src, dst, message);
+        let routable = src.ip() != dst.ip()
+            && self.hosts.contains_key(&src.ip())
+            && self.hosts.contains_key(&dst.ip());
+
+        if routable {
+            let envelope = Envelope { src, dst, message };
+            let verdict = match &mut self.filter {
+                Some(filter) => filter(&envelope),
+                None => Verdict::Deliver,
+            };
+
+            self.send(rand, trace, envelope, verdict);
             Ok(())
         } else {
             Err(Error::new(
@@ -229,43 +497,254 @@ impl Topology {
         }
     }
 
-    // Move messages from any network links to the `dst` host.
-    pub(crate) fn deliver_messages(&mut self, rand: &mut dyn RngCore, dst: &mut Host) {
-        for (pair, link) in &mut self.links {
-            if pair.0 == dst.addr || pair.1 == dst.addr {
-                link.deliver_messages(&self.config, rand, dst);
+    // Put `envelope` on the link between its hosts.
+    fn send(
+        &mut self,
+        rand: &mut dyn RngCore,
+        trace: &mut Recorder,
+        envelope: Envelope,
+        verdict: Verdict,
+    ) {
+        let pair = Pair::new(envelope.src.ip(), envelope.dst.ip());
+        self.link(pair.0, pair.1);
+
+        let link = &mut self.links[&pair];
+        link.enqueue_message(
+            &self.config,
+            rand,
+            trace,
+            &mut self.deliverable,
+            envelope,
+            verdict,
+        );
+
+        if !link.sent.is_empty() && self.busy.insert(pair.clone()) {
+            self.hosts[&pair.0].busy.insert(pair.1);
+            self.hosts[&pair.1].busy.insert(pair.0);
+        }
+    }
+
+    // Move messages from any network links to the `dst` host, returning
+    // whether any were delivered.
+    //
+    // Messages are delivered in the order they were due, across all links.
+    // Messages from different hosts that were due at the same time are
+    // ordered randomly, and those from the same host in the order they were
+    // sent.
+    pub(crate) fn deliver_messages(
+        &mut self,
+        rand: &mut dyn RngCore,
+        trace: &mut Recorder,
+        host: &mut Host,
+    ) -> bool {
+        let Some(mut deliverable) = self.deliverable.swap_remove(&host.addr) else {
+            return false;
+        };
+
+        let first = deliverable[0].envelope.src.ip();
+        let mut priority = IndexMap::new();
+        if deliverable.iter().any(|d| d.envelope.src.ip() != first) {
+            for d in &deliverable {
+                priority
+                    .entry(d.envelope.src.ip())
+                    .or_insert_with(|| rand.next_u64());
+            }
+        }
+
+        // Stable, so that ties from the same host keep their send order
+        deliverable.sort_by_key(|d| (d.at, priority.get(&d.envelope.src.ip()).copied()));
+
+        for message in deliverable.into_iter().map(|d| d.envelope) {
+            let (src, dst) = (message.src, message.dst);
+            trace.deliver(src, dst, &message.message);
+
+            let len = message.message.payload_len() as u64;
+            let link = &mut self.links[&Pair::new(src.ip(), dst.ip())];
+            let stats = &mut link.directions[direction(src.ip(), dst.ip())].stats;
+            stats.messages_delivered += 1;
+            stats.bytes_delivered += len;
+            host.stats.messages_received += 1;
+            host.stats.bytes_received += len;
+
+            if let Err(message) = host.receive_from_network(message) {
+                trace.message(dst, src, &message, Event::Rst);
+                link.directions[direction(dst.ip(), src.ip())].stats.rsts += 1;
+                host.stats.rsts += 1;
+
+                let envelope = Envelope {
+                    src: dst,
+                    dst: src,
+                    message,
+                };
+                self.send(rand, trace, envelope, Verdict::Deliver);
             }
         }
+
+        true
+    }
+
+    pub(crate) fn set_filter(&mut self, filter: Option<NetworkFilter>) {
+        self.filter = filter;
+    }
+
+    pub(crate) fn set_link_duplicate_rate(&mut self, from: IpAddr, to: IpAddr, value: f64) {
+        let global = self.config.delivery().clone();
+        self.direction(from, to).delivery(&global).duplicate_rate = value;
+    }
+
+    pub(crate) fn set_link_reorder_rate(&mut self, from: IpAddr, to: IpAddr, value: f64) {
+        let global = self.config.delivery().clone();
+        self.direction(from, to).delivery(&global).reorder_rate = value;
+    }
+
+    pub(crate) fn set_link_corruption(
+        &mut self,
+        from: IpAddr,
+        to: IpAddr,
+        value: config::Corruption,
+    ) {
+        self.direction(from, to).config.corruption = Some(value);
+    }
+
+    pub(crate) fn set_link_bandwidth(&mut self, from: IpAddr, to: IpAddr, value: Option<u64>) {
+        let global = self.config.bandwidth().clone();
+        self.direction(from, to).bandwidth(&global).bytes_per_sec = value;
+    }
+
+    pub(crate) fn hold(&mut self, from: IpAddr, to: IpAddr) {
+        self.direction(from, to).state = State::Hold;
     }
 
-    pub(crate) fn hold(&mut self, a: IpAddr, b: IpAddr) {
-        self.links[&Pair::new(a, b)].hold();
+    pub(crate) fn release(&mut self, trace: &mut Recorder, from: IpAddr, to: IpAddr) {
+        self.link(from, to).release(trace, from, to);
     }
 
-    pub(crate) fn release(&mut self, a: IpAddr, b: IpAddr) {
-        self.links[&Pair::new(a, b)].release();
+    pub(crate) fn partition(&mut self, from: IpAddr, to: IpAddr) {
+        self.direction(from, to).state = State::ExplicitPartition;
     }
 
-    pub(crate) fn partition(&mut self, a: IpAddr, b: IpAddr) {
-        self.links[&Pair::new(a, b)].explicit_partition();
+    // Repair the direction, without releasing any held messages.
+    pub(crate) fn repair(&mut self, from: IpAddr, to: IpAddr) {
+        self.direction(from, to).state = State::Healthy;
     }
 
-    pub(crate) fn repair(&mut self, a: IpAddr, b: IpAddr) {
-        self.links[&Pair::new(a, b)].explicit_repair();
+    fn direction(&mut self, from: IpAddr, to: IpAddr) -> &mut Direction {
+        &mut self.link(from, to).directions[direction(from, to)]
     }
 
     pub(crate) fn tick_by(&mut self, duration: Duration) {
         let _ = self.rt.tick(duration);
-        for link in self.links.values_mut() {
-            link.tick(self.rt.now());
+        let now = self.rt.now();
+
+        let links = &mut self.links;
+        let hosts = &mut self.hosts;
+        let deliverable = &mut self.deliverable;
+        self.busy.retain(|pair| {
+            let link = &mut links[pair];
+            link.tick(now, deliverable);
+
+            let busy = !link.sent.is_empty();
+            if !busy {
+                hosts[&pair.0].busy.swap_remove(&pair.1);
+                hosts[&pair.1].busy.swap_remove(&pair.0);
+            }
+            busy
+        });
+    }
+
+    /// How long until the next message is due to be delivered, if any are in
+    /// flight. Held messages are not due.
+    pub(crate) fn next_delivery(&self) -> Option<Duration> {
+        if !self.deliverable.is_empty() {
+            return Some(Duration::ZERO);
+        }
+
+        let now = self.rt.now();
+        self.busy
+            .iter()
+            .filter_map(|pair| self.links[pair].next_delivery(now, |_| true))
+            .min()
+    }
+
+    /// How long `dst` is certain not to receive any messages: until the next
+    /// message in flight to it is due, and no longer than the minimum latency
+    /// of the links to it, which messages sent from now on take at least.
+    /// Held messages may be released at any time.
+    pub(crate) fn quiet_period(&self, dst: IpAddr) -> Duration {
+        if self.deliverable.contains_key(&dst) {
+            return Duration::ZERO;
+        }
+
+        let global = self.config.latency();
+        let now = self.rt.now();
+        let mut quiet = global.min_message_latency;
+
+        let Some(peers) = self.hosts.get(&dst) else {
+            return quiet;
+        };
+
+        for &src in &peers.latency {
+            let link = &self.links[&Pair::new(src, dst)];
+            let config = &link.directions[direction(src, dst)].config;
+            if let Some(latency) = &config.latency {
+                quiet = quiet.min(latency.min_message_latency);
+            }
+        }
+
+        for &src in &peers.busy {
+            let link = &self.links[&Pair::new(src, dst)];
+            let held = link
+                .sent
+                .iter()
+                .any(|sent| sent.dst.ip() == dst && matches!(sent.status, DeliveryStatus::Hold));
+            if held {
+                return Duration::ZERO;
+            }
+
+            if let Some(next) = link.next_delivery(now, |sent| sent.dst.ip() == dst) {
+                quiet = quiet.min(next);
+            }
         }
+
+        quiet
     }
 
-    pub(crate) fn iter_mut(&mut self) -> LinksIter {
+    pub(crate) fn iter_mut(&mut self) -> LinksIter<'_> {
         LinksIter {
+            now: self.rt.now(),
+            start: self.start,
             iter: self.links.iter_mut(),
         }
     }
+
+    /// Remove messages dropped with [`SentRef::drop`] from all links.
+    pub(crate) fn remove_dropped(&mut self, trace: &mut Recorder) {
+        for pair in &self.busy {
+            let link = &mut self.links[pair];
+            let directions = &mut link.directions;
+
+            link.sent.retain(|sent| {
+                let dropped = matches!(sent.status, DeliveryStatus::Dropped);
+                if dropped {
+                    trace.message(sent.src, sent.dst, &sent.protocol, Event::Drop);
+                    directions[direction(sent.src.ip(), sent.dst.ip())]
+                        .stats
+                        .dropped += 1;
+                }
+                !dropped
+            });
+        }
+    }
+
+    /// Add the counters for each direction of each link to `stats`.
+    pub(crate) fn stats(&self, stats: &mut Stats) {
+        for (pair, link) in &self.links {
+            for (from, to) in [(pair.0, pair.1), (pair.1, pair.0)] {
+                let direction = &link.directions[direction(from, to)];
+                stats.links.insert((from, to), direction.stats.clone());
+            }
+        }
+    }
 }
 
 struct Sent {
@@ -273,6 +752,9 @@ struct Sent {
     dst: SocketAddr,
     status: DeliveryStatus,
     protocol: Protocol,
+
+    /// When the message was put on the link.
+    enqueued_at: Instant,
 }
 
 impl Sent {
@@ -284,15 +766,14 @@ impl Sent {
 enum DeliveryStatus {
     DeliverAfter(Instant),
     Hold,
+    Dropped,
 }
 
 impl Link {
     fn new(now: Instant) -> Link {
         Link {
-            state: State::Healthy,
-            config: config::Link::default(),
+            directions: [Direction::new(), Direction::new()],
             sent: VecDeque::new(),
-            deliverable: IndexMap::new(),
             now,
         }
     }
@@ -301,42 +782,156 @@ impl Link {
         &mut self,
         global_config: &config::Link,
         rand: &mut dyn RngCore,
-        src: SocketAddr,
-        dst: SocketAddr,
-        message: Protocol,
+        trace: &mut Recorder,
+        deliverable: &mut Deliverables,
+        envelope: Envelope,
+        verdict: Verdict,
     ) {
+        let Envelope {
+            src,
+            dst,
+            mut message,
+        } = envelope;
+
         tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %message, "Send");
+        trace.send(src, dst, &message);
+
+        let stats = &mut self.directions[direction(src.ip(), dst.ip())].stats;
+        stats.messages_sent += 1;
+        stats.bytes_sent += message.payload_len() as u64;
+
+        self.rand_partition_or_repair(global_config, rand, trace, src.ip(), dst.ip());
+
+        // A partitioned link drops messages, even those a filter would hold
+        let partitioned = matches!(
+            self.directions[direction(src.ip(), dst.ip())].state,
+            State::ExplicitPartition | State::RandPartition
+        );
+        let verdict = match verdict {
+            Verdict::Hold if partitioned => Verdict::Drop,
+            verdict => verdict,
+        };
+
+        let mut delay = Duration::ZERO;
+        match verdict {
+            Verdict::Deliver => {}
+            Verdict::Drop => {
+                tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %message, "Drop");
+                trace.message(src, dst, &message, Event::Drop);
+                self.directions[direction(src.ip(), dst.ip())].stats.dropped += 1;
+
+                return;
+            }
+            Verdict::Delay(d) => delay = d,
+            Verdict::Hold => {
+                tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %message, "Hold");
+                trace.message(src, dst, &message, Event::Hold);
+                self.directions[direction(src.ip(), dst.ip())].stats.held += 1;
+
+                self.sent.push_back(Sent {
+                    src,
+                    dst,
+                    status: DeliveryStatus::Hold,
+                    protocol: message,
+                    enqueued_at: self.now,
+                });
+                return;
+            }
+            Verdict::Duplicate => {
+                if let Protocol::Udp(datagram) = &message {
+                    tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %message, "Duplicate");
+                    trace.message(src, dst, &message, Event::Duplicate);
+
+                    let message = Protocol::Udp(Datagram(datagram.0.clone()));
+                    let envelope = Envelope { src, dst, message };
+                    self.enqueue(global_config, rand, trace, envelope, delay);
+                }
+            }
+            Verdict::Rewrite(payload) => message.set_payload(payload),
+        }
 
-        self.rand_partition_or_repair(global_config, rand);
-        self.enqueue(global_config, rand, src, dst, message);
-        self.process_deliverables();
+        let envelope = Envelope { src, dst, message };
+        self.enqueue(global_config, rand, trace, envelope, delay);
+        self.process_deliverables(deliverable);
     }
 
     // src -> link -> dst
     //        ^-- you are here!
     //
     // Messages may be dropped, sit on the link for a while (due to latency, or
-    // because the link has stalled), or be delivered immediately.
+    // because the link has stalled), or be delivered immediately. Delivery of
+    // healthy messages is pushed back by `extra_delay`.
     fn enqueue(
         &mut self,
         global_config: &config::Link,
         rand: &mut dyn RngCore,
-        src: SocketAddr,
-        dst: SocketAddr,
-        message: Protocol,
+        trace: &mut Recorder,
+        envelope: Envelope,
+        extra_delay: Duration,
     ) {
-        let status = match self.state {
+        let Envelope {
+            src,
+            dst,
+            mut message,
+        } = envelope;
+        let direction = &mut self.directions[direction(src.ip(), dst.ip())];
+
+        if let State::Healthy = direction.state {
+            if direction.rand_loss(global_config.message_loss(), rand) {
+                tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %message, "Loss");
+                trace.message(src, dst, &message, Event::Loss);
+                direction.stats.lost += 1;
+
+                return;
+            }
+        }
+
+        let status = match direction.state {
             State::Healthy => {
-                let delay = self.delay(global_config.latency(), rand);
-                DeliveryStatus::DeliverAfter(self.now + delay)
+                if direction.rand_corrupt(global_config.corruption(), rand, &mut message) {
+                    tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %message, "Corrupt");
+                    trace.message(src, dst, &message, Event::Corrupt);
+                }
+
+                let sent_at = direction.transmit(global_config.bandwidth(), self.now, &message);
+                let mut delay = direction.delay(global_config.latency(), rand) + extra_delay;
+
+                if let Protocol::Udp(datagram) = &message {
+                    if direction.rand_reorder(global_config.delivery(), rand) {
+                        tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %message, "Reorder");
+                        trace.message(src, dst, &message, Event::Reorder);
+
+                        delay += direction.max_latency(global_config.latency());
+                    }
+
+                    if direction.rand_duplicate(global_config.delivery(), rand) {
+                        tracing::trace!(target: TRACING_TARGET, ?src, ?dst, protocol = %message, "Duplicate");
+                        trace.message(src, dst, &message, Event::Duplicate);
+
+                        let delay = direction.delay(global_config.latency(), rand) + extra_delay;
+                        self.sent.push_back(Sent {
+                            src,
+                            dst,
+                            status: DeliveryStatus::DeliverAfter(sent_at + delay),
+                            protocol: Protocol::Udp(Datagram(datagram.0.clone())),
+                            enqueued_at: self.now,
+                        });
+                    }
+                }
+
+                DeliveryStatus::DeliverAfter(sent_at + delay)
             }
             State::Hold => {
                 tracing::trace!(target: TRACING_TARGET,?src, ?dst, protocol = %message, "Hold");
+                trace.message(src, dst, &message, Event::Hold);
+                direction.stats.held += 1;
 
                 DeliveryStatus::Hold
             }
             _ => {
                 tracing::trace!(target: TRACING_TARGET,?src, ?dst, protocol = %message, "Drop");
+                trace.message(src, dst, &message, Event::Drop);
+                direction.stats.dropped += 1;
 
                 return;
             }
@@ -347,17 +942,20 @@ impl Link {
             dst,
             status,
             protocol: message,
+            enqueued_at: self.now,
         };
 
         self.sent.push_back(sent);
     }
 
-    fn tick(&mut self, now: Instant) {
+    fn tick(&mut self, now: Instant, deliverable: &mut Deliverables) {
         self.now = now;
-        self.process_deliverables();
+        self.process_deliverables(deliverable);
     }
 
-    fn process_deliverables(&mut self) {
+    // Move messages that are due from the link to their destination's queue
+    // in `deliverables`.
+    fn process_deliverables(&mut self, deliverables: &mut Deliverables) {
         // TODO: `drain_filter` is not yet stable, and so we have a low quality
         // implementation here that avoids clones.
         let mut deliverable = 0;
@@ -367,106 +965,203 @@ impl Link {
             if let DeliveryStatus::DeliverAfter(time) = sent.status {
                 if time <= self.now {
                     let sent = self.sent.remove(index).unwrap();
+                    self.directions[direction(sent.src.ip(), sent.dst.ip())]
+                        .stats
+                        .latency
+                        .record(self.now - sent.enqueued_at);
+
                     let envelope = Envelope {
                         src: sent.src,
                         dst: sent.dst,
                         message: sent.protocol,
                     };
-                    self.deliverable
+                    deliverables
                         .entry(sent.dst.ip())
                         .or_default()
-                        .push_back(envelope);
+                        .push(Deliverable { at: time, envelope });
                     deliverable += 1;
                 }
             }
         }
     }
 
-    // FIXME: This implementation does not respect message delivery order. If
-    // host A and host B are ordered (by addr), and B sends before A, then this
-    // method will deliver A's message before B's.
-    fn deliver_messages(
+    /// How long until the next message on the link for which `f` returns
+    /// true is due to be delivered, if any. Held messages are not due.
+    fn next_delivery(&self, now: Instant, f: impl Fn(&Sent) -> bool) -> Option<Duration> {
+        self.sent
+            .iter()
+            .filter_map(|sent| match sent.status {
+                DeliveryStatus::DeliverAfter(at) if f(sent) => {
+                    Some(at.saturating_duration_since(now))
+                }
+                _ => None,
+            })
+            .min()
+    }
+
+    // Randomly break or repair the direction of this link from `src` to `dst`.
+    fn rand_partition_or_repair(
         &mut self,
         global_config: &config::Link,
         rand: &mut dyn RngCore,
-        host: &mut Host,
+        trace: &mut Recorder,
+        src: IpAddr,
+        dst: IpAddr,
     ) {
-        let deliverable = self
-            .deliverable
-            .entry(host.addr)
-            .or_default()
-            .drain(..)
-            .collect::<Vec<Envelope>>();
-
-        for message in deliverable {
-            let (src, dst) = (message.src, message.dst);
-            if let Err(message) = host.receive_from_network(message) {
-                self.enqueue_message(global_config, rand, dst, src, message);
-            }
-        }
-    }
-
-    // Randomly break or repair this link.
-    fn rand_partition_or_repair(&mut self, global_config: &config::Link, rand: &mut dyn RngCore) {
-        match self.state {
-            State::Healthy => {
-                if self.rand_partition(global_config.message_loss(), rand) {
-                    self.state = State::RandPartition;
-                }
+        let direction = &mut self.directions[direction(src, dst)];
+        match direction.state {
+            State::Healthy if direction.rand_partition(global_config.message_loss(), rand) => {
+                direction.state = State::RandPartition;
             }
-            State::RandPartition => {
-                if self.rand_repair(global_config.message_loss(), rand) {
-                    self.release();
-                }
+            State::RandPartition if direction.rand_repair(global_config.message_loss(), rand) => {
+                self.release(trace, src, dst);
             }
             _ => {}
         }
     }
 
-    fn hold(&mut self) {
-        self.state = State::Hold;
-    }
-
-    // This link becomes healthy, and any held messages are scheduled for delivery.
-    fn release(&mut self) {
-        self.state = State::Healthy;
+    // The direction of this link from `src` to `dst` becomes healthy, and any
+    // messages held on it are scheduled for delivery.
+    fn release(&mut self, trace: &mut Recorder, src: IpAddr, dst: IpAddr) {
+        self.directions[direction(src, dst)].state = State::Healthy;
         for sent in &mut self.sent {
+            if sent.src.ip() != src {
+                continue;
+            }
+
             if let DeliveryStatus::Hold = sent.status {
+                trace.message(sent.src, sent.dst, &sent.protocol, Event::Release);
                 sent.deliver(self.now);
             }
         }
     }
+}
 
-    fn explicit_partition(&mut self) {
-        self.state = State::ExplicitPartition;
+impl Direction {
+    fn link_state(&self) -> LinkState {
+        match self.state {
+            State::Healthy => LinkState::Healthy,
+            State::ExplicitPartition | State::RandPartition => LinkState::Partitioned,
+            State::Hold => LinkState::Held,
+        }
     }
 
-    // Repair the link, without releasing any held messages.
-    fn explicit_repair(&mut self) {
-        self.state = State::Healthy;
+    fn new() -> Direction {
+        Direction {
+            state: State::Healthy,
+            config: config::Link::default(),
+            busy_until: None,
+            bursting: false,
+            stats: LinkStats::default(),
+        }
     }
 
-    /// Should the link be randomly partitioned
+    /// Should the direction be randomly partitioned
     fn rand_partition(&self, global: &config::MessageLoss, rand: &mut dyn RngCore) -> bool {
         let config = self.config.message_loss.as_ref().unwrap_or(global);
         let fail_rate = config.fail_rate;
         fail_rate > 0.0 && rand.gen_bool(fail_rate)
     }
 
+    /// Should an individual message be lost
+    fn rand_loss(&mut self, global: &config::MessageLoss, rand: &mut dyn RngCore) -> bool {
+        let config = self.config.message_loss.as_ref().unwrap_or(global);
+        let loss_rate = config.loss_rate;
+        let mut lost = loss_rate > 0.0 && rand.gen_bool(loss_rate);
+
+        if let Some(burst) = &config.burst_loss {
+            let transition = if self.bursting {
+                burst.bad_to_good
+            } else {
+                burst.good_to_bad
+            };
+            if transition > 0.0 && rand.gen_bool(transition) {
+                self.bursting = !self.bursting;
+            }
+
+            let loss = if self.bursting {
+                burst.bad_loss
+            } else {
+                burst.good_loss
+            };
+            lost |= loss > 0.0 && rand.gen_bool(loss);
+        }
+
+        lost
+    }
+
     fn rand_repair(&self, global: &config::MessageLoss, rand: &mut dyn RngCore) -> bool {
         let config = self.config.message_loss.as_ref().unwrap_or(global);
         let repair_rate = config.repair_rate;
         repair_rate > 0.0 && rand.gen_bool(repair_rate)
     }
 
-    fn delay(&self, global: &config::Latency, rand: &mut dyn RngCore) -> Duration {
-        let config = self.config.latency.as_ref().unwrap_or(global);
+    /// Randomly corrupt the payload of `message`, returning whether it was
+    /// corrupted.
+    fn rand_corrupt(
+        &self,
+        global: &config::Corruption,
+        rand: &mut dyn RngCore,
+        message: &mut Protocol,
+    ) -> bool {
+        let config = self.config.corruption.as_ref().unwrap_or(global);
+        let (rate, payload) = match message {
+            Protocol::Udp(Datagram(payload)) => (config.udp_rate, payload),
+            Protocol::Tcp(Segment::Data(_, payload)) => (config.tcp_rate, payload),
+            Protocol::Tcp(_) => return false,
+        };
+
+        if payload.is_empty() || config.kinds.is_empty() || rate <= 0.0 || !rand.gen_bool(rate) {
+            return false;
+        }
+
+        let kind = config.kinds[rand.gen_range(0..config.kinds.len())];
+        let corrupted = corrupt(kind, payload, rand);
+        message.set_payload(corrupted);
+        true
+    }
+
+    fn rand_duplicate(&self, global: &config::Delivery, rand: &mut dyn RngCore) -> bool {
+        let config = self.config.delivery.as_ref().unwrap_or(global);
+        let duplicate_rate = config.duplicate_rate;
+        duplicate_rate > 0.0 && rand.gen_bool(duplicate_rate)
+    }
+
+    fn rand_reorder(&self, global: &config::Delivery, rand: &mut dyn RngCore) -> bool {
+        let config = self.config.delivery.as_ref().unwrap_or(global);
+        let reorder_rate = config.reorder_rate;
+        reorder_rate > 0.0 && rand.gen_bool(reorder_rate)
+    }
+
+    /// Put `message` on the link, returning when it has been fully sent. With
+    /// limited bandwidth, messages queue behind those sent before them.
+    fn transmit(
+        &mut self,
+        global: &config::Bandwidth,
+        now: Instant,
+        message: &Protocol,
+    ) -> Instant {
+        let config = self.config.bandwidth.as_ref().unwrap_or(global);
+        let Some(bytes_per_sec) = config.bytes_per_sec else {
+            return now;
+        };
 
-        let mult = config.latency_distribution.sample(rand);
-        let range = (config.max_message_latency - config.min_message_latency).as_millis() as f64;
-        let delay = config.min_message_latency + Duration::from_millis((range * mult) as _);
+        let nanos = message.payload_len() as u128 * 1_000_000_000 / bytes_per_sec.max(1) as u128;
+        let start = self.busy_until.map_or(now, |busy| busy.max(now));
+        let sent_at = start + Duration::from_nanos(nanos as u64);
 
-        std::cmp::min(delay, config.max_message_latency)
+        self.busy_until = Some(sent_at);
+        sent_at
+    }
+
+    fn max_latency(&self, global: &config::Latency) -> Duration {
+        let config = self.config.latency.as_ref().unwrap_or(global);
+        config.max_message_latency
+    }
+
+    fn delay(&self, global: &config::Latency, rand: &mut dyn RngCore) -> Duration {
+        let config = self.config.latency.as_ref().unwrap_or(global);
+        config.sample(rand)
     }
 
     fn latency(&mut self, global: &config::Latency) -> &mut config::Latency {
@@ -478,4 +1173,33 @@ impl Link {
             .message_loss
             .get_or_insert_with(|| global.clone())
     }
+
+    fn delivery(&mut self, global: &config::Delivery) -> &mut config::Delivery {
+        self.config.delivery.get_or_insert_with(|| global.clone())
+    }
+
+    fn bandwidth(&mut self, global: &config::Bandwidth) -> &mut config::Bandwidth {
+        self.config.bandwidth.get_or_insert_with(|| global.clone())
+    }
+}
+
+fn corrupt(kind: CorruptionKind, payload: &Bytes, rand: &mut dyn RngCore) -> Bytes {
+    let mut bytes = payload.to_vec();
+
+    match kind {
+        CorruptionKind::BitFlip => {
+            let i = rand.gen_range(0..bytes.len());
+            bytes[i] ^= 1 << rand.gen_range(0..8);
+        }
+        CorruptionKind::Truncate => {
+            bytes.truncate(rand.gen_range(0..bytes.len()));
+        }
+        CorruptionKind::ZeroFill => {
+            let start = rand.gen_range(0..bytes.len());
+            let end = rand.gen_range(start..bytes.len()) + 1;
+            bytes[start..end].fill(0);
+        }
+    }
+
+    bytes.into()
 }
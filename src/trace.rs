@@ -0,0 +1,316 @@
+//! Recording of simulation events.
+//!
+//! When enabled with [`Builder::record_trace`], the simulation records every
+//! network event, host crash and bounce, and host tick as a typed [`Record`].
+//! Records are timestamped with simulated time, so traces can be compared
+//! across runs.
+//!
+//! [`Builder::record_trace`]: crate::Builder::record_trace
+
+use crate::envelope::{Protocol, Segment};
+use crate::pcap::Pcap;
+use crate::TRACING_TARGET;
+
+use std::fmt::Write as _;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+/// A recorded sequence of simulation events, in the order they occurred.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    records: Vec<Record>,
+}
+
+/// A single simulation event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Simulated time since the simulation started.
+    pub elapsed: Duration,
+
+    /// What happened.
+    pub event: Event,
+}
+
+/// Simulation events captured by a [`Trace`].
+///
+/// Link events apply to messages sent from the first host to the second.
+/// Two-way changes, such as [`Sim::partition`], are recorded once for each
+/// direction.
+///
+/// [`Sim::partition`]: crate::Sim::partition
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// A message was sent onto the network.
+    Send(Message),
+
+    /// A message was dropped by a partitioned link, or a network filter.
+    Drop(Message),
+
+    /// A message was held by the link, or a network filter.
+    Hold(Message),
+
+    /// A message was lost by a lossy, but otherwise healthy, link.
+    Loss(Message),
+
+    /// A message's payload was corrupted by the link. The message describes
+    /// the corrupted payload.
+    Corrupt(Message),
+
+    /// A datagram was duplicated by the link, or a network filter. Both copies
+    /// are delivered.
+    Duplicate(Message),
+
+    /// A datagram was delayed by the link, so that datagrams sent after it may
+    /// be delivered first.
+    Reorder(Message),
+
+    /// A held message was released for delivery.
+    Release(Message),
+
+    /// A message was delivered to its destination host.
+    Deliver(Message),
+
+    /// Delivery of a TCP message failed and a RST is sent in response.
+    Rst(Message),
+
+    /// The host's software was crashed.
+    Crash(IpAddr),
+
+    /// The host's software was restarted.
+    Bounce(IpAddr),
+
+    /// The host's software was given a chance to run.
+    Tick(IpAddr),
+
+    /// The link from one host to another was partitioned.
+    Partition(IpAddr, IpAddr),
+
+    /// The link from one host to another was repaired.
+    Repair(IpAddr, IpAddr),
+
+    /// The link from one host to another started holding messages.
+    HoldLink(IpAddr, IpAddr),
+
+    /// The link from one host to another stopped holding messages.
+    ReleaseLink(IpAddr, IpAddr),
+
+    /// The message latency of the link from one host to another was set.
+    LinkLatency(IpAddr, IpAddr, Duration),
+
+    /// The message latency of the link from one host to another was restored
+    /// to its previous configuration.
+    RestoreLinkLatency(IpAddr, IpAddr),
+}
+
+/// A summary of a message on the network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// Where the message was sent from.
+    pub src: SocketAddr,
+
+    /// Where the message is sent to.
+    pub dst: SocketAddr,
+
+    /// Protocol and segment kind, e.g. `TCP SYN` or `UDP`.
+    pub kind: &'static str,
+
+    /// Length of the payload in bytes.
+    pub len: usize,
+}
+
+impl Trace {
+    /// The recorded events.
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Write the trace as JSON lines, one record per line.
+    pub fn write_json_lines(&self, mut w: impl io::Write) -> io::Result<()> {
+        for record in &self.records {
+            writeln!(w, "{}", record.to_json())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Record {
+    /// Serialize the record as a single line JSON object.
+    pub fn to_json(&self) -> String {
+        let mut json = format!("{{\"elapsed_ns\":{}", self.elapsed.as_nanos());
+
+        let message = |json: &mut String, event, m: &Message| {
+            let _ = write!(
+                json,
+                ",\"event\":\"{event}\",\"src\":\"{}\",\"dst\":\"{}\",\"kind\":\"{}\",\"len\":{}",
+                m.src, m.dst, m.kind, m.len
+            );
+        };
+        let host = |json: &mut String, event, h: &IpAddr| {
+            let _ = write!(json, ",\"event\":\"{event}\",\"host\":\"{h}\"");
+        };
+        let link = |json: &mut String, event, from: &IpAddr, to: &IpAddr| {
+            let _ = write!(
+                json,
+                ",\"event\":\"{event}\",\"from\":\"{from}\",\"to\":\"{to}\""
+            );
+        };
+
+        match &self.event {
+            Event::Send(m) => message(&mut json, "send", m),
+            Event::Drop(m) => message(&mut json, "drop", m),
+            Event::Hold(m) => message(&mut json, "hold", m),
+            Event::Loss(m) => message(&mut json, "loss", m),
+            Event::Corrupt(m) => message(&mut json, "corrupt", m),
+            Event::Duplicate(m) => message(&mut json, "duplicate", m),
+            Event::Reorder(m) => message(&mut json, "reorder", m),
+            Event::Release(m) => message(&mut json, "release", m),
+            Event::Deliver(m) => message(&mut json, "deliver", m),
+            Event::Rst(m) => message(&mut json, "rst", m),
+            Event::Crash(h) => host(&mut json, "crash", h),
+            Event::Bounce(h) => host(&mut json, "bounce", h),
+            Event::Tick(h) => host(&mut json, "tick", h),
+            Event::Partition(a, b) => link(&mut json, "partition", a, b),
+            Event::Repair(a, b) => link(&mut json, "repair", a, b),
+            Event::HoldLink(a, b) => link(&mut json, "hold_link", a, b),
+            Event::ReleaseLink(a, b) => link(&mut json, "release_link", a, b),
+            Event::LinkLatency(a, b, latency) => {
+                link(&mut json, "link_latency", a, b);
+                let _ = write!(json, ",\"latency_ns\":{}", latency.as_nanos());
+            }
+            Event::RestoreLinkLatency(a, b) => link(&mut json, "restore_link_latency", a, b),
+        }
+
+        json.push('}');
+        json
+    }
+}
+
+impl Message {
+    pub(crate) fn new(src: SocketAddr, dst: SocketAddr, protocol: &Protocol) -> Message {
+        let kind = match protocol {
+            Protocol::Tcp(Segment::Syn(_)) => "TCP SYN",
+            Protocol::Tcp(Segment::Data(..)) => "TCP DATA",
+            Protocol::Tcp(Segment::Fin(_)) => "TCP FIN",
+            Protocol::Tcp(Segment::Ack(..)) => "TCP ACK",
+            Protocol::Tcp(Segment::Rst) => "TCP RST",
+            Protocol::Udp(_) => "UDP",
+        };
+
+        Message {
+            src,
+            dst,
+            kind,
+            len: protocol.payload_len(),
+        }
+    }
+}
+
+/// Records events into a [`Trace`], if enabled, and delivered messages into a
+/// pcap capture, if configured.
+#[derive(Default)]
+pub(crate) struct Recorder {
+    enabled: bool,
+
+    /// Simulated time used to timestamp new records. Updated by the
+    /// simulation as it steps.
+    pub(crate) elapsed: Duration,
+
+    trace: Trace,
+
+    pcap: Option<Pcap>,
+}
+
+impl Recorder {
+    pub(crate) fn new(enabled: bool, pcap: Option<Pcap>) -> Recorder {
+        Recorder {
+            enabled,
+            pcap,
+            ..Default::default()
+        }
+    }
+
+    /// Record the event built by `f`. The closure is only invoked when
+    /// recording is enabled.
+    pub(crate) fn record(&mut self, f: impl FnOnce() -> Event) {
+        if self.enabled {
+            self.trace.records.push(Record {
+                elapsed: self.elapsed,
+                event: f(),
+            });
+        }
+    }
+
+    /// Record a message event for `protocol` sent from `src` to `dst`.
+    pub(crate) fn message(
+        &mut self,
+        src: SocketAddr,
+        dst: SocketAddr,
+        protocol: &Protocol,
+        f: impl FnOnce(Message) -> Event,
+    ) {
+        self.record(|| f(Message::new(src, dst, protocol)));
+    }
+
+    /// Record a message being sent onto the network.
+    pub(crate) fn send(&mut self, src: SocketAddr, dst: SocketAddr, protocol: &Protocol) {
+        self.message(src, dst, protocol, Event::Send);
+
+        if let Some(pcap) = &mut self.pcap {
+            pcap.send(src, dst, protocol);
+        }
+    }
+
+    /// Record a message being delivered to its destination host.
+    pub(crate) fn deliver(&mut self, src: SocketAddr, dst: SocketAddr, protocol: &Protocol) {
+        self.message(src, dst, protocol, Event::Deliver);
+
+        if let Some(pcap) = &mut self.pcap {
+            // Stop capturing rather than failing the simulation
+            if let Err(error) = pcap.deliver(self.elapsed, src, dst, protocol) {
+                tracing::error!(target: TRACING_TARGET, %error, "Failed to write pcap, disabling capture");
+                self.pcap = None;
+            }
+        }
+    }
+
+    pub(crate) fn trace(&self) -> &Trace {
+        &self.trace
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Event, Message, Record};
+    use std::time::Duration;
+
+    #[test]
+    fn json() {
+        let record = Record {
+            elapsed: Duration::from_millis(3),
+            event: Event::Send(Message {
+                src: "192.168.0.1:49152".parse().unwrap(),
+                dst: "192.168.0.2:1234".parse().unwrap(),
+                kind: "UDP",
+                len: 5,
+            }),
+        };
+
+        assert_eq!(
+            r#"{"elapsed_ns":3000000,"event":"send","src":"192.168.0.1:49152","dst":"192.168.0.2:1234","kind":"UDP","len":5}"#,
+            record.to_json()
+        );
+
+        let record = Record {
+            elapsed: Duration::ZERO,
+            event: Event::Crash("192.168.0.1".parse().unwrap()),
+        };
+
+        assert_eq!(
+            r#"{"elapsed_ns":0,"event":"crash","host":"192.168.0.1"}"#,
+            record.to_json()
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use crate::config::Config;
 use crate::envelope::Protocol;
 use crate::ip::IpVersionAddrIter;
+use crate::schedule::Fault;
+use crate::trace::{Event, Recorder};
 use crate::{config, for_pairs, Dns, Host, ToIpAddr, ToIpAddrs, Topology, TRACING_TARGET};
 
 use indexmap::IndexMap;
@@ -28,6 +30,9 @@ pub(crate) struct World {
     /// Random number generator used for all decisions. To make execution
     /// determinstic, reuse the same seed.
     pub(crate) rng: Box<dyn RngCore>,
+
+    /// Records simulation events, if enabled.
+    pub(crate) trace: Recorder,
 }
 
 scoped_thread_local!(static CURRENT: RefCell<World>);
@@ -38,6 +43,7 @@ impl World {
         link: config::Link,
         rng: Box<dyn RngCore>,
         addrs: IpVersionAddrIter,
+        trace: Recorder,
     ) -> World {
         World {
             hosts: IndexMap::new(),
@@ -45,6 +51,7 @@ impl World {
             dns: Dns::new(addrs),
             current: None,
             rng,
+            trace,
         }
     }
 
@@ -88,7 +95,13 @@ impl World {
     }
 
     pub(crate) fn hold(&mut self, a: IpAddr, b: IpAddr) {
-        self.topology.hold(a, b);
+        self.hold_oneway(a, b);
+        self.hold_oneway(b, a);
+    }
+
+    pub(crate) fn hold_oneway(&mut self, from: IpAddr, to: IpAddr) {
+        self.trace.record(|| Event::HoldLink(from, to));
+        self.topology.hold(from, to);
     }
 
     pub(crate) fn hold_many(&mut self, a: impl ToIpAddrs, b: impl ToIpAddrs) {
@@ -100,8 +113,23 @@ impl World {
         });
     }
 
+    pub(crate) fn hold_oneway_many(&mut self, from: impl ToIpAddrs, to: impl ToIpAddrs) {
+        let from = self.lookup_many(from);
+        let to = self.lookup_many(to);
+
+        for_pairs(&from, &to, |from, to| {
+            self.hold_oneway(from, to);
+        });
+    }
+
     pub(crate) fn release(&mut self, a: IpAddr, b: IpAddr) {
-        self.topology.release(a, b);
+        self.release_oneway(a, b);
+        self.release_oneway(b, a);
+    }
+
+    pub(crate) fn release_oneway(&mut self, from: IpAddr, to: IpAddr) {
+        self.trace.record(|| Event::ReleaseLink(from, to));
+        self.topology.release(&mut self.trace, from, to);
     }
 
     pub(crate) fn release_many(&mut self, a: impl ToIpAddrs, b: impl ToIpAddrs) {
@@ -113,8 +141,23 @@ impl World {
         });
     }
 
+    pub(crate) fn release_oneway_many(&mut self, from: impl ToIpAddrs, to: impl ToIpAddrs) {
+        let from = self.lookup_many(from);
+        let to = self.lookup_many(to);
+
+        for_pairs(&from, &to, |from, to| {
+            self.release_oneway(from, to);
+        });
+    }
+
     pub(crate) fn partition(&mut self, a: IpAddr, b: IpAddr) {
-        self.topology.partition(a, b);
+        self.partition_oneway(a, b);
+        self.partition_oneway(b, a);
+    }
+
+    pub(crate) fn partition_oneway(&mut self, from: IpAddr, to: IpAddr) {
+        self.trace.record(|| Event::Partition(from, to));
+        self.topology.partition(from, to);
     }
 
     pub(crate) fn partition_many(&mut self, a: impl ToIpAddrs, b: impl ToIpAddrs) {
@@ -126,8 +169,23 @@ impl World {
         });
     }
 
+    pub(crate) fn partition_oneway_many(&mut self, from: impl ToIpAddrs, to: impl ToIpAddrs) {
+        let from = self.lookup_many(from);
+        let to = self.lookup_many(to);
+
+        for_pairs(&from, &to, |from, to| {
+            self.partition_oneway(from, to);
+        });
+    }
+
     pub(crate) fn repair(&mut self, a: IpAddr, b: IpAddr) {
-        self.topology.repair(a, b);
+        self.repair_oneway(a, b);
+        self.repair_oneway(b, a);
+    }
+
+    pub(crate) fn repair_oneway(&mut self, from: IpAddr, to: IpAddr) {
+        self.trace.record(|| Event::Repair(from, to));
+        self.topology.repair(from, to);
     }
 
     pub(crate) fn repair_many(&mut self, a: impl ToIpAddrs, b: impl ToIpAddrs) {
@@ -139,8 +197,67 @@ impl World {
         });
     }
 
+    pub(crate) fn repair_oneway_many(&mut self, from: impl ToIpAddrs, to: impl ToIpAddrs) {
+        let from = self.lookup_many(from);
+        let to = self.lookup_many(to);
+
+        for_pairs(&from, &to, |from, to| {
+            self.repair_oneway(from, to);
+        });
+    }
+
+    /// Apply a scheduled network fault.
+    ///
+    /// Crashes and bounces operate on host software, so they are applied by the
+    /// [`Sim`](crate::Sim).
+    pub(crate) fn apply_fault(&mut self, fault: Fault) {
+        match fault {
+            Fault::Partition(a, b) => {
+                let (a, b) = (a.to_ip_addrs(&mut self.dns), b.to_ip_addrs(&mut self.dns));
+                for_pairs(&a, &b, |a, b| self.partition(a, b));
+            }
+            Fault::Repair(a, b) => {
+                let (a, b) = (a.to_ip_addrs(&mut self.dns), b.to_ip_addrs(&mut self.dns));
+                for_pairs(&a, &b, |a, b| self.repair(a, b));
+            }
+            Fault::Hold(a, b) => {
+                let (a, b) = (a.to_ip_addrs(&mut self.dns), b.to_ip_addrs(&mut self.dns));
+                for_pairs(&a, &b, |a, b| self.hold(a, b));
+            }
+            Fault::Release(a, b) => {
+                let (a, b) = (a.to_ip_addrs(&mut self.dns), b.to_ip_addrs(&mut self.dns));
+                for_pairs(&a, &b, |a, b| self.release(a, b));
+            }
+            Fault::Latency(a, b, value, saved) => {
+                let (a, b) = (a.to_ip_addrs(&mut self.dns), b.to_ip_addrs(&mut self.dns));
+                for_pairs(&a, &b, |a, b| {
+                    for (from, to) in [(a, b), (b, a)] {
+                        let previous = self.topology.link_latency(from, to);
+                        saved.borrow_mut().push((from, to, previous));
+
+                        self.trace.record(|| Event::LinkLatency(from, to, value));
+                        self.topology.set_link_message_latency(from, to, value);
+                    }
+                });
+            }
+            Fault::RestoreLatency(saved) => {
+                for (from, to, previous) in saved.take() {
+                    self.trace.record(|| Event::RestoreLinkLatency(from, to));
+                    self.topology.set_link_latency(from, to, previous);
+                }
+            }
+            Fault::Crash(_) | Fault::Bounce(_) => unreachable!("applied by the sim"),
+        }
+    }
+
     /// Register a new host with the simulation.
-    pub(crate) fn register(&mut self, addr: IpAddr, nodename: &str, config: &Config) {
+    pub(crate) fn register(
+        &mut self,
+        addr: IpAddr,
+        nodename: &str,
+        config: &Config,
+        since_epoch: Duration,
+    ) {
         assert!(
             !self.hosts.contains_key(&addr),
             "already registered host for the given ip address"
@@ -148,15 +265,21 @@ impl World {
 
         tracing::info!(target: TRACING_TARGET, nodename, ?addr, "New");
 
-        // Register links between the new host and all existing hosts
-        for existing in self.hosts.keys() {
-            self.topology.register(*existing, addr);
-        }
+        // Links to other hosts are created when first used
+        self.topology.register(addr);
 
         // Initialize host state
         self.hosts.insert(
             addr,
-            Host::new(addr, config.tcp_capacity, config.udp_capacity),
+            Host::new(
+                addr,
+                since_epoch,
+                config.tcp_capacity,
+                config.tcp_window,
+                config.tcp_retransmission,
+                config.tcp_connect_timeout,
+                config.udp_capacity,
+            ),
         );
     }
 
@@ -168,15 +291,43 @@ impl World {
         dst: SocketAddr,
         message: Protocol,
     ) -> Result<()> {
+        let len = message.payload_len() as u64;
+
         self.topology
-            .enqueue_message(&mut self.rng, src, dst, message)
+            .enqueue_message(&mut self.rng, &mut self.trace, src, dst, message)?;
+
+        // Only messages that made it onto the network count as sent
+        if let Some(host) = self.hosts.get_mut(&src.ip()) {
+            host.stats.messages_sent += 1;
+            host.stats.bytes_sent += len;
+        }
+
+        Ok(())
+    }
+
+    /// How far the runtime of the host at `addr` may move ahead of the
+    /// simulation, while idle: no further than a message could arrive for it,
+    /// and not at all while another host may wake it directly by accepting
+    /// its connection.
+    pub(crate) fn lookahead(&self, addr: IpAddr) -> Duration {
+        if self.hosts[&addr].tcp.is_connecting() {
+            return Duration::ZERO;
+        }
+
+        self.topology.quiet_period(addr)
     }
 
     /// Tick the host at `addr` by `duration`.
     pub(crate) fn tick(&mut self, addr: IpAddr, duration: Duration) {
-        self.hosts
+        let segments = self
+            .hosts
             .get_mut(&addr)
             .expect("missing host")
             .tick(duration);
+
+        // Acks and retransmissions are lost if the peer is unreachable
+        for (pair, segment) in segments {
+            let _ = self.send_message(pair.local, pair.remote, Protocol::Tcp(segment));
+        }
     }
 }
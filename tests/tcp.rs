@@ -15,7 +15,7 @@ use tokio::{
 use turmoil::{
     lookup,
     net::{TcpListener, TcpStream},
-    Builder, IpVersion, Result,
+    Builder, Corruption, CorruptionKind, IpVersion, Result, Retransmission,
 };
 
 const PORT: u16 = 1738;
@@ -52,7 +52,7 @@ fn network_partitions_during_connect() -> Result {
 
         assert_error_kind(
             TcpStream::connect(("server", PORT)).await,
-            io::ErrorKind::ConnectionRefused,
+            io::ErrorKind::TimedOut,
         );
 
         turmoil::repair("client", "server");
@@ -684,6 +684,239 @@ fn split() -> Result {
     sim.run()
 }
 
+#[test]
+fn bandwidth_limit() -> Result {
+    let mut sim = Builder::new()
+        .bandwidth(1_000)
+        .simulation_duration(Duration::from_secs(60))
+        .build();
+
+    sim.client("server", async move {
+        let listener = bind().await?;
+        let (mut s, _) = listener.accept().await?;
+
+        let start = tokio::time::Instant::now();
+
+        let mut buf = vec![0; 10_000];
+        s.read_exact(&mut buf).await?;
+
+        // the small write queues behind the large one
+        assert_eq!(1, s.read_u8().await?);
+        assert!(start.elapsed() >= Duration::from_secs(10));
+
+        Ok(())
+    });
+
+    sim.host("client", || async move {
+        let mut s = TcpStream::connect(("server", PORT)).await?;
+
+        s.write_all(&[0; 10_000]).await?;
+        s.write_u8(1).await?;
+
+        future::pending().await
+    });
+
+    sim.run()
+}
+
+#[test]
+fn write_waits_for_window() -> Result {
+    let mut sim = Builder::new().tcp_window(1024).build();
+
+    sim.client("server", async move {
+        let listener = bind().await?;
+        let (mut s, _) = listener.accept().await?;
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let mut buf = vec![0; 4096];
+        s.read_exact(&mut buf).await?;
+        assert!(buf[..1024].iter().all(|&b| b == 1));
+        assert!(buf[1024..].iter().all(|&b| b == 2));
+
+        Ok(())
+    });
+
+    sim.client("client", async move {
+        let mut s = TcpStream::connect(("server", PORT)).await?;
+
+        s.write_all(&[1; 1024]).await?;
+
+        // the server is not reading, so its window is full
+        let write = timeout(Duration::from_millis(500), s.write_u8(2)).await;
+        assert!(write.is_err());
+
+        let start = tokio::time::Instant::now();
+        s.write_all(&[2; 3072]).await?;
+        assert!(start.elapsed() >= Duration::from_millis(400));
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+#[test]
+fn small_writes_do_not_overflow_receiver() -> Result {
+    let mut sim = Builder::new().build();
+
+    sim.client("server", async move {
+        let listener = bind().await?;
+        let (mut s, _) = listener.accept().await?;
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let mut buf = vec![0; 1000];
+        s.read_exact(&mut buf).await?;
+
+        Ok(())
+    });
+
+    sim.client("client", async move {
+        let mut s = TcpStream::connect(("server", PORT)).await?;
+
+        // far more segments than the tcp capacity
+        for _ in 0..1000 {
+            s.write_u8(1).await?;
+        }
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+#[test]
+fn lost_window_update_is_resent() -> Result {
+    let mut sim = Builder::new().build();
+
+    sim.client("server", async move {
+        let listener = bind().await?;
+        let (mut s, _) = listener.accept().await?;
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        // the window update for these reads is lost
+        turmoil::partition("server", "client");
+
+        let mut buf = vec![0; 64 * 1024];
+        s.read_exact(&mut buf).await?;
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        turmoil::repair("server", "client");
+
+        assert_eq!(2, s.read_u8().await?);
+
+        Ok(())
+    });
+
+    sim.client("client", async move {
+        let mut s = TcpStream::connect(("server", PORT)).await?;
+
+        s.write_all(&[1; 64 * 1024]).await?;
+
+        timeout(Duration::from_secs(5), s.write_all(&[2])).await??;
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+#[test]
+fn tcp_not_corrupted_by_default() -> Result {
+    let mut sim = Builder::new()
+        .corruption(Corruption {
+            udp_rate: 1.0,
+            ..Default::default()
+        })
+        .build();
+
+    sim.client("server", async move {
+        let listener = bind().await?;
+        let (mut s, _) = listener.accept().await?;
+
+        let mut buf = [0; 16];
+        s.read_exact(&mut buf).await?;
+        assert_eq!([0xff; 16], buf);
+
+        Ok(())
+    });
+
+    sim.client("client", async move {
+        let mut s = TcpStream::connect(("server", PORT)).await?;
+        s.write_all(&[0xff; 16]).await?;
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+#[test]
+fn tcp_corruption() -> Result {
+    let mut sim = Builder::new()
+        .corruption(Corruption {
+            tcp_rate: 1.0,
+            kinds: vec![CorruptionKind::ZeroFill],
+            ..Default::default()
+        })
+        .build();
+
+    sim.client("server", async move {
+        let listener = bind().await?;
+        let (mut s, _) = listener.accept().await?;
+
+        let mut buf = [0; 16];
+        s.read_exact(&mut buf).await?;
+        assert!(buf.contains(&0));
+
+        Ok(())
+    });
+
+    sim.client("client", async move {
+        let mut s = TcpStream::connect(("server", PORT)).await?;
+        s.write_all(&[0xff; 16]).await?;
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+#[test]
+fn tcp_truncation_keeps_window_open() -> Result {
+    let mut sim = Builder::new()
+        .tcp_window(1024)
+        .corruption(Corruption {
+            tcp_rate: 1.0,
+            kinds: vec![CorruptionKind::Truncate],
+            ..Default::default()
+        })
+        .build();
+
+    sim.client("server", async move {
+        let listener = bind().await?;
+        let (mut s, _) = listener.accept().await?;
+
+        // truncated segments are zero-filled to their sent length
+        let mut buf = vec![0; 4096];
+        s.read_exact(&mut buf).await?;
+        assert!(buf.contains(&0));
+
+        Ok(())
+    });
+
+    sim.client("client", async move {
+        let mut s = TcpStream::connect(("server", PORT)).await?;
+        s.write_all(&[0xff; 4096]).await?;
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
 // # IpVersion specific tests
 
 #[test]
@@ -1041,3 +1274,128 @@ fn socket_to_nonexistent_node() -> Result {
     });
     sim.run()
 }
+
+#[test]
+fn retransmit_after_partition() -> Result {
+    let mut sim = Builder::new()
+        .tcp_retransmission(Retransmission::default())
+        .build();
+
+    sim.client("server", async move {
+        let listener = bind().await?;
+        let (mut s, _) = listener.accept().await?;
+
+        let mut buf = vec![0; 3];
+        s.read_exact(&mut buf).await?;
+        assert_eq!(vec![1, 2, 3], buf);
+
+        s.write_u8(4).await?;
+
+        Ok(())
+    });
+
+    sim.client("client", async move {
+        let mut s = TcpStream::connect(("server", PORT)).await?;
+
+        s.write_u8(1).await?;
+
+        turmoil::partition("client", "server");
+        s.write_u8(2).await?;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        turmoil::repair("client", "server");
+
+        s.write_u8(3).await?;
+        assert_eq!(4, s.read_u8().await?);
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+#[test]
+fn retransmission_timeout() -> Result {
+    let mut sim = Builder::new()
+        .tcp_retransmission(Retransmission {
+            rto: Duration::from_millis(100),
+            timeout: Duration::from_secs(2),
+        })
+        .simulation_duration(Duration::from_secs(10))
+        .build();
+
+    sim.client("server", async move {
+        let listener = bind().await?;
+        let (mut s, _) = listener.accept().await?;
+
+        // the client's RST is lost to the partition
+        assert!(timeout(Duration::from_secs(5), s.read_u8()).await.is_err());
+
+        Ok(())
+    });
+
+    sim.client("client", async move {
+        let mut s = TcpStream::connect(("server", PORT)).await?;
+
+        turmoil::partition("client", "server");
+        s.write_u8(1).await?;
+
+        let start = tokio::time::Instant::now();
+        assert_error_kind(s.read_u8().await, ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_secs(2));
+
+        turmoil::repair("client", "server");
+        assert_error_kind(s.write_u8(2).await, ErrorKind::TimedOut);
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+#[test]
+fn connect_retransmits_syn() -> Result {
+    let mut sim = Builder::new().build();
+
+    sim.host("server", || async {
+        let listener = bind().await?;
+        loop {
+            let _ = listener.accept().await;
+        }
+    });
+
+    sim.client("client", async {
+        turmoil::partition("client", "server");
+
+        let connect = tokio::spawn(TcpStream::connect(("server", PORT)));
+
+        // the SYNs sent at 0s and 1s are lost, the one at 3s gets through
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        turmoil::repair("client", "server");
+
+        let start = tokio::time::Instant::now();
+        connect.await.unwrap()?;
+        assert!(start.elapsed() >= Duration::from_millis(1400));
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+#[test]
+fn connect_refused_when_not_listening() -> Result {
+    let mut sim = Builder::new().build();
+
+    sim.host("server", || async { future::pending().await });
+
+    sim.client("client", async {
+        assert_error_kind(
+            TcpStream::connect(("server", PORT)).await,
+            io::ErrorKind::ConnectionRefused,
+        );
+
+        Ok(())
+    });
+
+    sim.run()
+}
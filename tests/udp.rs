@@ -1,4 +1,5 @@
 use std::{
+    cell::RefCell,
     io::{self, ErrorKind},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     rc::Rc,
@@ -10,7 +11,8 @@ use tokio::{sync::oneshot, time::timeout};
 use turmoil::{
     lookup,
     net::{self, UdpSocket},
-    Builder, IpVersion, Result,
+    Builder, Corruption, CorruptionKind, Datagram, IpVersion, LatencyDistribution, Protocol,
+    Result, Verdict,
 };
 
 const PORT: u16 = 1738;
@@ -252,6 +254,482 @@ fn network_partition() -> Result {
     sim.run()
 }
 
+#[test]
+fn oneway_partition() -> Result {
+    let mut sim = Builder::new().build();
+
+    sim.client("server", async {
+        let sock = bind().await?;
+
+        // the ping arrives, but the pong is dropped
+        let origin = recv_ping(&sock).await?;
+        send_pong(&sock, origin).await
+    });
+
+    sim.client("client", async {
+        turmoil::partition_oneway("server", "client");
+
+        let sock = bind().await?;
+        send_ping(&sock).await?;
+
+        assert!(timeout(Duration::from_secs(1), recv_pong(&sock))
+            .await
+            .is_err());
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+#[test]
+fn oneway_hold_and_release() -> Result {
+    let mut sim = Builder::new().build();
+
+    sim.host("server", || async {
+        let sock = bind().await?;
+
+        while let Ok(origin) = recv_ping(&sock).await {
+            let _ = send_pong(&sock, origin).await;
+        }
+
+        Ok(())
+    });
+
+    sim.client("client", async {
+        turmoil::hold_oneway("client", "server");
+
+        let sock = bind().await?;
+        send_ping(&sock).await?;
+
+        let res = timeout(Duration::from_secs(1), recv_pong(&sock)).await;
+        assert!(res.is_err());
+
+        // holding the other direction does not release the ping
+        turmoil::release_oneway("server", "client");
+
+        let res = timeout(Duration::from_secs(1), recv_pong(&sock)).await;
+        assert!(res.is_err());
+
+        turmoil::release_oneway("client", "server");
+
+        recv_pong(&sock).await
+    });
+
+    sim.run()
+}
+
+#[test]
+fn oneway_latency() -> Result {
+    let latency = Duration::from_millis(10);
+
+    let mut sim = Builder::new()
+        .min_message_latency(latency)
+        .max_message_latency(latency)
+        .build();
+
+    sim.host("server", || async {
+        let sock = bind().await?;
+
+        while let Ok(origin) = recv_ping(&sock).await {
+            let _ = send_pong(&sock, origin).await;
+        }
+
+        Ok(())
+    });
+
+    sim.client("client", async {
+        let sock = bind().await?;
+
+        let start = tokio::time::Instant::now();
+        send_ping(&sock).await?;
+        recv_pong(&sock).await?;
+
+        let rtt = start.elapsed();
+        assert!(rtt >= Duration::from_millis(510), "{rtt:?}");
+        assert!(rtt < Duration::from_millis(520), "{rtt:?}");
+
+        Ok(())
+    });
+
+    sim.set_link_latency_oneway("client", "server", Duration::from_millis(500));
+
+    sim.run()
+}
+
+#[test]
+fn duplicate_datagrams() -> Result {
+    let mut sim = Builder::new().duplicate_rate(1.0).build();
+
+    sim.client("server", async {
+        let sock = bind().await?;
+
+        recv_ping(&sock).await?;
+        recv_ping(&sock).await?;
+
+        Ok(())
+    });
+
+    sim.client("client", async {
+        let sock = bind().await?;
+        send_ping(&sock).await
+    });
+
+    sim.run()
+}
+
+#[test]
+fn reorder_datagrams() -> Result {
+    let latency = Duration::from_millis(10);
+
+    let mut sim = Builder::new()
+        .seed(42)
+        .min_message_latency(latency)
+        .max_message_latency(latency)
+        .reorder_rate(0.5)
+        .build();
+
+    sim.client("server", async {
+        let sock = bind().await?;
+        let mut received = vec![];
+
+        for _ in 0..20 {
+            let mut buf = [0; 1];
+            sock.recv_from(&mut buf).await?;
+            received.push(buf[0]);
+        }
+
+        let mut sorted = received.clone();
+        sorted.sort();
+        assert_eq!((0..20).collect::<Vec<_>>(), sorted);
+        assert_ne!(sorted, received);
+
+        Ok(())
+    });
+
+    sim.client("client", async {
+        let sock = bind().await?;
+
+        for i in 0..20 {
+            sock.send_to(&[i], (lookup("server"), PORT)).await?;
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
+/// Sends 200 numbered datagrams from the client, returning the numbers the
+/// server received.
+fn lossy_transfer(mut sim: turmoil::Sim<'_>) -> Result<Vec<u8>> {
+    let received = Rc::new(RefCell::new(vec![]));
+
+    let r = received.clone();
+    sim.host("server", move || {
+        let r = r.clone();
+
+        async move {
+            let sock = bind().await?;
+
+            loop {
+                let mut buf = [0; 1];
+                sock.recv_from(&mut buf).await?;
+                r.borrow_mut().push(buf[0]);
+            }
+        }
+    });
+
+    sim.client("client", async {
+        let sock = bind().await?;
+
+        for i in 0..200 {
+            sock.send_to(&[i], (lookup("server"), PORT)).await?;
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        Ok(())
+    });
+
+    sim.run()?;
+
+    let received = received.borrow().clone();
+    Ok(received)
+}
+
+#[test]
+fn loss_rate() -> Result {
+    let sim = Builder::new().seed(1).loss_rate(0.5).build();
+    let received = lossy_transfer(sim)?;
+
+    assert!((60..140).contains(&received.len()), "{}", received.len());
+    // the link stays up, so messages are still delivered at the end
+    assert!(received.iter().any(|&i| i >= 190));
+
+    Ok(())
+}
+
+#[test]
+fn burst_loss() -> Result {
+    let sim = Builder::new()
+        .seed(1)
+        .burst_loss(turmoil::BurstLoss {
+            good_to_bad: 0.05,
+            bad_to_good: 0.2,
+            good_loss: 0.0,
+            bad_loss: 1.0,
+        })
+        .build();
+
+    let mut received = lossy_transfer(sim)?;
+    received.sort();
+
+    let gaps = received
+        .windows(2)
+        .map(|w| w[1] - w[0] - 1)
+        .filter(|&gap| gap > 0)
+        .collect::<Vec<_>>();
+
+    let lost = gaps.iter().map(|&gap| gap as usize).sum::<usize>();
+    assert!(lost > 0);
+    // losses are correlated, coming in bursts
+    assert!(lost / gaps.len() >= 2, "{gaps:?}");
+
+    Ok(())
+}
+
+#[test]
+fn network_filter() -> Result {
+    let mut sim = Builder::new()
+        .latency_distribution(LatencyDistribution::Fixed(Duration::from_millis(10)))
+        .build();
+
+    sim.client("server", async {
+        let sock = bind().await?;
+
+        let mut received = vec![];
+        while let Ok(Ok((_, _))) = timeout(Duration::from_secs(1), async {
+            let mut buf = [0; 1];
+            let res = sock.recv_from(&mut buf).await;
+            received.push(buf[0]);
+            res
+        })
+        .await
+        {}
+
+        // 2 is dropped, 3 rewritten, 4 duplicated and 0 delayed
+        assert_eq!(vec![1, 42, 4, 4, 5, 0], received);
+
+        Ok(())
+    });
+
+    sim.client("client", async {
+        let sock = bind().await?;
+
+        for i in 0..6 {
+            sock.send_to(&[i], (lookup("server"), PORT)).await?;
+        }
+
+        Ok(())
+    });
+
+    let server = sim.lookup("server");
+    let mut sent = 0;
+    sim.set_network_filter(move |envelope| {
+        if envelope.dst().ip() != server {
+            return Verdict::Deliver;
+        }
+
+        sent += 1;
+        match sent {
+            1 => Verdict::Delay(Duration::from_millis(100)),
+            3 => Verdict::Drop,
+            4 => Verdict::Rewrite(vec![42].into()),
+            5 => Verdict::Duplicate,
+            _ => Verdict::Deliver,
+        }
+    });
+
+    sim.run()
+}
+
+#[test]
+fn network_filter_hold() -> Result {
+    let mut sim = Builder::new().build();
+
+    sim.client("server", async {
+        let sock = bind().await?;
+        let mut buf = [0; 1];
+
+        // the held datagram arrives once released
+        for i in [1, 0] {
+            let (_, _) = sock.recv_from(&mut buf).await?;
+            assert_eq!(i, buf[0]);
+        }
+
+        Ok(())
+    });
+
+    sim.client("client", async {
+        let sock = bind().await?;
+        sock.send_to(&[0], (lookup("server"), PORT)).await?;
+        sock.send_to(&[1], (lookup("server"), PORT)).await?;
+
+        Ok(())
+    });
+
+    sim.set_network_filter(|envelope| match envelope.message() {
+        Protocol::Udp(Datagram(payload)) if payload[..] == [0] => Verdict::Hold,
+        _ => Verdict::Deliver,
+    });
+
+    for _ in 0..500 {
+        sim.step()?;
+    }
+
+    sim.clear_network_filter();
+    sim.release("client", "server");
+    sim.run()
+}
+
+#[test]
+fn network_filter_hold_on_partitioned_link() -> Result {
+    let mut sim = Builder::new().build();
+
+    sim.host("server", || async {
+        let sock = bind().await?;
+        let mut buf = [0; 1];
+
+        loop {
+            sock.recv_from(&mut buf).await?;
+        }
+    });
+
+    sim.client("client", async {
+        let sock = bind().await?;
+        sock.send_to(&[0], (lookup("server"), PORT)).await?;
+
+        Ok(())
+    });
+
+    sim.partition("client", "server");
+    sim.set_network_filter(|_| Verdict::Hold);
+    sim.run()?;
+
+    // the partition drops the datagram, so there is nothing to release
+    sim.repair("client", "server");
+    sim.release("client", "server");
+    for _ in 0..10 {
+        sim.step()?;
+    }
+
+    let stats = sim.stats();
+    let link = stats
+        .link(sim.lookup("client"), sim.lookup("server"))
+        .unwrap();
+    assert_eq!(1, link.dropped);
+    assert_eq!(0, link.held);
+    assert_eq!(0, link.messages_delivered);
+
+    Ok(())
+}
+
+#[test]
+fn stats() -> Result {
+    let mut sim = Builder::new().udp_capacity(4).build();
+
+    sim.client("server", async {
+        let _sock = bind().await?;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        Ok(())
+    });
+
+    sim.client("client", async {
+        let sock = bind().await?;
+
+        for _ in 0..10 {
+            sock.send_to(b"ping", (lookup("server"), PORT)).await?;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        turmoil::partition("client", "server");
+        for _ in 0..3 {
+            sock.send_to(b"ping", (lookup("server"), PORT)).await?;
+        }
+
+        // unroutable, so never sent
+        let res = sock.send_to(b"ping", "192.168.0.200:80").await;
+        assert_error_kind(res, ErrorKind::ConnectionRefused);
+
+        Ok(())
+    });
+
+    sim.run()?;
+
+    let stats = sim.stats();
+    let (client, server) = (sim.lookup("client"), sim.lookup("server"));
+
+    let link = stats.link(client, server).unwrap();
+    assert_eq!(13, link.messages_sent);
+    assert_eq!(52, link.bytes_sent);
+    assert_eq!(10, link.messages_delivered);
+    assert_eq!(3, link.dropped);
+    assert_eq!(10, link.latency.count());
+    assert!(link.latency.max().unwrap() <= Duration::from_millis(100));
+
+    assert_eq!(0, stats.link(server, client).unwrap().messages_sent);
+
+    let server = stats.host(server).unwrap();
+    assert_eq!(10, server.messages_received);
+    assert_eq!(6, server.udp_dropped_full);
+
+    assert_eq!(13, stats.host(client).unwrap().messages_sent);
+    assert_eq!(13, stats.network().messages_sent);
+
+    Ok(())
+}
+
+#[test]
+fn corruption() -> Result {
+    let mut sim = Builder::new()
+        .corruption(Corruption {
+            udp_rate: 1.0,
+            kinds: vec![CorruptionKind::ZeroFill],
+            ..Default::default()
+        })
+        .build();
+
+    sim.client("server", async {
+        let sock = bind().await?;
+
+        for _ in 0..10 {
+            let mut buf = [0; 16];
+            let (n, _) = sock.recv_from(&mut buf).await?;
+
+            assert_eq!(16, n);
+            assert!(buf.contains(&0));
+        }
+
+        Ok(())
+    });
+
+    sim.client("client", async {
+        let sock = bind().await?;
+
+        for _ in 0..10 {
+            sock.send_to(&[0xff; 16], (lookup("server"), PORT)).await?;
+        }
+
+        Ok(())
+    });
+
+    sim.run()
+}
+
 #[test]
 fn bounce() -> Result {
     // The server publishes the number of requests it thinks it processed into